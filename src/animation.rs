@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use bevy::asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+use crate::{AppState, GameState};
+use crate::entity::{Motion, ShotEvent};
+use crate::entity::player::Reloading;
+
+// clip names the game picks on its own, a set doesn't need to have all of them
+pub const IDLE: &str = "idle";
+pub const WALK: &str = "walk";
+pub const SHOOT: &str = "shoot";
+pub const RELOAD: &str = "reload";
+pub const DIE: &str = "die";
+
+// length of Motion.speed above which an entity counts as walking
+const WALK_SPEED: f32 = 0.15;
+// seconds a corpse stays on the last frame of its die clip
+const CORPSE_TIME: f32 = 4.;
+
+// named clips of a sprite sheet
+#[derive(TypeUuid)]
+#[uuid = "3d8f2b6e-9a41-4c7d-b5e2-1f6a0c9d8e73"]
+pub struct AnimationSet {
+    pub atlas: Handle<TextureAtlas>,
+    pub clips: HashMap<String, Clip>,
+}
+
+#[derive(Deserialize)]
+struct AnimationFile {
+    texture: String,
+    // size of a single frame in pixels
+    frame_size: (f32, f32),
+    columns: usize,
+    rows: usize,
+    clips: HashMap<String, Clip>,
+}
+
+#[derive(Deserialize)]
+pub struct Clip {
+    // indices on the sheet, left to right and top to bottom
+    pub frames: Vec<usize>,
+    // seconds each frame is shown
+    pub frame_time: f32,
+    // clips that don't loop stay on their last frame once done
+    #[serde(default)]
+    pub looping: bool,
+}
+
+#[derive(Default)]
+pub struct AnimationLoader;
+
+impl AssetLoader for AnimationLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let file: AnimationFile = ron::de::from_bytes(bytes)?;
+            let texture = AssetPath::new(PathBuf::from(&file.texture), None);
+            let atlas = TextureAtlas::from_grid(
+                load_context.get_handle(texture.clone()),
+                Vec2::new(file.frame_size.0, file.frame_size.1),
+                file.columns,
+                file.rows,
+            );
+            let atlas = load_context.set_labeled_asset("atlas", LoadedAsset::new(atlas).with_dependencies(vec![texture]));
+            load_context.set_default_asset(LoadedAsset::new(AnimationSet {
+                atlas,
+                clips: file.clips,
+            }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["anim.ron"]
+    }
+}
+
+// plays clips of `set` on the entity's `TextureAtlasSprite`
+#[derive(Component)]
+pub struct Animator {
+    pub set: Handle<AnimationSet>,
+    clip: String,
+    // index in the clip's frames
+    frame: usize,
+    // seconds the current frame has been shown
+    elapsed: f32,
+    finished: bool,
+}
+
+impl Animator {
+    pub fn new(set: Handle<AnimationSet>, clip: &str) -> Self {
+        Self {
+            set,
+            clip: clip.to_string(),
+            frame: 0,
+            elapsed: 0.,
+            finished: false,
+        }
+    }
+
+    // switches to `clip` unless it's already playing
+    pub fn play(&mut self, clip: &str) {
+        if self.clip != clip {
+            self.restart(clip);
+        }
+    }
+
+    // plays `clip` from its first frame, even when it's the current one
+    pub fn restart(&mut self, clip: &str) {
+        self.clip = clip.to_string();
+        self.frame = 0;
+        self.elapsed = 0.;
+        self.finished = false;
+    }
+
+    pub fn clip(&self) -> &str {
+        &self.clip
+    }
+
+    // whether a clip that doesn't loop reached its end, or the set doesn't have the clip
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+}
+
+// what is left of a dead enemy, plays the die clip and goes away after `CORPSE_TIME`
+#[derive(Component)]
+pub struct Corpse(Timer);
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(SystemSet::on_update(AppState::Game(GameState::Playing))
+                .with_system(weapon_clips.label("animation").after("shoot").after("reload").after("ai"))
+                .with_system(movement_clips.label("animation").after("movement"))
+                .with_system(animate.after("animation"))
+                .with_system(remove_corpses.after(animate))
+            );
+        // the player's die clip plays behind the game over screen
+        app.add_system_set(SystemSet::on_update(AppState::Game(GameState::GameOver))
+            .with_system(animate)
+        );
+        app.add_system_set(SystemSet::on_enter(AppState::Menu)
+            .with_system(despawn_corpses)
+        );
+    }
+}
+
+// leaves the die clip of a dying entity behind, so the entity itself can be despawned right away
+pub fn spawn_corpse(
+    commands: &mut Commands,
+    animator: &Animator,
+    sprite: &TextureAtlasSprite,
+    atlas: &Handle<TextureAtlas>,
+    transform: &Transform,
+) {
+    let mut transform = *transform;
+    // below the living
+    transform.translation.z = 0.45;
+    commands.spawn_bundle(SpriteSheetBundle {
+        sprite: TextureAtlasSprite {
+            color: Color::WHITE,
+            ..sprite.clone()
+        },
+        texture_atlas: atlas.clone(),
+        transform,
+        ..default()
+    })
+        .insert(Animator::new(animator.set.clone(), DIE))
+        .insert(Corpse(Timer::from_seconds(CORPSE_TIME, false)))
+        .insert(Name::new("Corpse"));
+}
+
+fn animate(
+    time: Res<Time>,
+    sets: Res<Assets<AnimationSet>>,
+    mut q_animator: Query<(&mut Animator, &mut TextureAtlasSprite, &mut Handle<TextureAtlas>)>,
+) {
+    for (mut animator, mut sprite, mut atlas) in q_animator.iter_mut() {
+        let set = match sets.get(&animator.set) {
+            Some(s) => s,
+            None => continue,
+        };
+        // entities are spawned with an empty atlas, the set knows which one to use
+        if *atlas != set.atlas {
+            *atlas = set.atlas.clone();
+        }
+        let clip = match set.clips.get(&animator.clip) {
+            Some(c) if !c.frames.is_empty() => c,
+            _ => {
+                // lets `movement_clips` pick something else
+                animator.finished = true;
+                continue;
+            }
+        };
+        animator.elapsed += time.delta_seconds();
+        while !animator.finished && clip.frame_time > 0. && animator.elapsed >= clip.frame_time {
+            animator.elapsed -= clip.frame_time;
+            if animator.frame + 1 < clip.frames.len() {
+                animator.frame += 1;
+            } else if clip.looping {
+                animator.frame = 0;
+            } else {
+                animator.finished = true;
+            }
+        }
+        let index = clip.frames[animator.frame.min(clip.frames.len() - 1)];
+        if sprite.index != index {
+            sprite.index = index;
+        }
+    }
+}
+
+// idle or walk depending on how fast the entity moves, other clips play out first
+fn movement_clips(
+    mut q_animator: Query<(&mut Animator, Option<&Motion>), Without<Corpse>>,
+) {
+    for (mut animator, motion) in q_animator.iter_mut() {
+        if !matches!(animator.clip(), IDLE | WALK) && !animator.finished() {
+            continue;
+        }
+        // guns have no motion of their own
+        let walking = motion.is_some_and(|m| m.speed.length() > WALK_SPEED);
+        animator.play(if walking { WALK } else { IDLE });
+    }
+}
+
+// shots and reloads animate both the gun and whoever holds it
+fn weapon_clips(
+    mut shot_events: EventReader<ShotEvent>,
+    q_reload: Query<(Entity, &Parent), Added<Reloading>>,
+    mut q_animator: Query<&mut Animator>,
+) {
+    let mut restart = |ent: Entity, clip: &str| {
+        if let Ok(mut animator) = q_animator.get_mut(ent) {
+            animator.restart(clip);
+        }
+    };
+    for event in shot_events.iter() {
+        restart(event.shooter, SHOOT);
+        if let Some(gun) = event.gun {
+            restart(gun, SHOOT);
+        }
+    }
+    for (gun, parent) in q_reload.iter() {
+        restart(gun, RELOAD);
+        restart(parent.get(), RELOAD);
+    }
+}
+
+fn remove_corpses(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q_corpse: Query<(Entity, &Animator, &mut Corpse)>,
+) {
+    for (ent, animator, mut corpse) in q_corpse.iter_mut() {
+        if !animator.finished() {
+            continue;
+        }
+        if corpse.0.tick(time.delta()).finished() {
+            commands.entity(ent).despawn();
+        }
+    }
+}
+
+fn despawn_corpses(
+    mut commands: Commands,
+    q_ent: Query<Entity, With<Corpse>>,
+) {
+    for ent in q_ent.iter() {
+        commands.entity(ent).despawn_recursive();
+    }
+}
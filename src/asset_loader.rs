@@ -1,6 +1,12 @@
 use bevy::prelude::*;
 use bevy_asset_loader::prelude::{LoadingState, LoadingStateAppExt, AssetCollection};
+use bevy_kira_audio::AudioSource;
 use crate::AppState;
+use crate::animation::{AnimationLoader, AnimationSet};
+use crate::entity::boss::{BossDefinitions, BossesLoader};
+use crate::level::{Level, LevelLoader};
+use crate::wave::{WaveDefinitions, WavesLoader};
+use crate::weapon::{WeaponDefinitions, WeaponsLoader};
 
 #[derive(AssetCollection)]
 pub struct FontAssets {
@@ -16,8 +22,6 @@ pub struct FontAssets {
 
 #[derive(AssetCollection)]
 pub struct TextureAssets {
-    #[asset(path = "textures/player.png")]
-    pub player: Handle<Image>,
     #[asset(path = "textures/buttons/start.png")]
     pub b_start: Handle<Image>,
     #[asset(path = "textures/buttons/start-pressed.png")]
@@ -28,25 +32,78 @@ pub struct TextureAssets {
     pub b_quit_pressed: Handle<Image>,
     #[asset(path = "textures/title.png")]
     pub title: Handle<Image>,
-    #[asset(path = "textures/guns/basic-gun.png")]
-    pub basic_gun: Handle<Image>,
-    #[asset(path = "textures/bullets/basic-bullet.png")]
-    pub basic_bullet: Handle<Image>,
-    #[asset(path = "textures/guns/rocket-gun.png")]
-    pub rocket_gun: Handle<Image>,
-    #[asset(path = "textures/bullets/rocket-bullet.png")]
-    pub rocket_bullet: Handle<Image>,
+    #[asset(path = "textures/explosion.png")]
+    pub explosion: Handle<Image>,
+    #[asset(path = "textures/tileset.png")]
+    pub tileset: Handle<Image>,
+    #[asset(path = "textures/health.png")]
+    pub health: Handle<Image>,
+    #[asset(path = "textures/ammo.png")]
+    pub ammo: Handle<Image>,
+}
+
+// characters, gun animations are in their `WeaponDefinition`
+#[derive(AssetCollection)]
+pub struct AnimationAssets {
+    #[asset(path = "data/animations/player.anim.ron")]
+    pub player: Handle<AnimationSet>,
+    #[asset(path = "data/animations/zombie.anim.ron")]
+    pub zombie: Handle<AnimationSet>,
+    #[asset(path = "data/animations/skeleton.anim.ron")]
+    pub skeleton: Handle<AnimationSet>,
+    #[asset(path = "data/animations/mutant.anim.ron")]
+    pub mutant: Handle<AnimationSet>,
+}
+
+// weapon sounds are in their `WeaponDefinition`
+#[derive(AssetCollection)]
+pub struct AudioAssets {
+    #[asset(path = "sounds/explosion.wav")]
+    pub explosion: Handle<AudioSource>,
+    #[asset(path = "sounds/roar.wav")]
+    pub roar: Handle<AudioSource>,
+    #[asset(path = "sounds/click.wav")]
+    pub click: Handle<AudioSource>,
+    #[asset(path = "music/menu.wav")]
+    pub menu_music: Handle<AudioSource>,
+    #[asset(path = "music/game.wav")]
+    pub game_music: Handle<AudioSource>,
+}
+
+#[derive(AssetCollection)]
+pub struct DataAssets {
+    #[asset(path = "data/default.weapons.ron")]
+    pub weapons: Handle<WeaponDefinitions>,
+    #[asset(path = "data/default.waves.ron")]
+    pub waves: Handle<WaveDefinitions>,
+    #[asset(path = "data/default.bosses.ron")]
+    pub bosses: Handle<BossDefinitions>,
+    #[asset(path = "data/levels/arena.level.ron")]
+    pub level: Handle<Level>,
 }
 
 pub struct AssetsPlugin;
 
 impl Plugin for AssetsPlugin {
     fn build(&self, app: &mut App) {
+        app.add_asset::<WeaponDefinitions>();
+        app.init_asset_loader::<WeaponsLoader>();
+        app.add_asset::<WaveDefinitions>();
+        app.init_asset_loader::<WavesLoader>();
+        app.add_asset::<BossDefinitions>();
+        app.init_asset_loader::<BossesLoader>();
+        app.add_asset::<AnimationSet>();
+        app.init_asset_loader::<AnimationLoader>();
+        app.add_asset::<Level>();
+        app.init_asset_loader::<LevelLoader>();
         app.add_loading_state(
             LoadingState::new(AppState::LoadingAssets)
                 .continue_to_state(AppState::Loading)
                 .with_collection::<FontAssets>()
                 .with_collection::<TextureAssets>()
+                .with_collection::<AudioAssets>()
+                .with_collection::<AnimationAssets>()
+                .with_collection::<DataAssets>()
         );
     }
 }
\ No newline at end of file
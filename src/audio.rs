@@ -0,0 +1,342 @@
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use bevy_kira_audio::{AudioApp, AudioChannel, AudioSource};
+use crate::AppState;
+use crate::asset_loader::AudioAssets;
+
+// seconds the old track fades out while the new one fades in
+const CROSSFADE: f32 = 1.5;
+// distances in half view widths from the camera, full volume up to the first, silent from the second
+const FULL_VOLUME_DISTANCE: f32 = 0.6;
+const SILENT_DISTANCE: f32 = 3.;
+// how far to one side a sound at the view edge is panned, 0.5 is all the way
+const PAN_STRENGTH: f32 = 0.35;
+// sfx voices, one `add_voice` each in `GameAudioPlugin`, the first `LOOP_VOICES` are kept for
+// looping sounds and the others take turns playing one shot sounds, see `Voice`
+const VOICES: usize = 8;
+const LOOP_VOICES: usize = 2;
+
+// volume is only set per channel, so the new track fades in on one of these while the old one
+// fades out on the other
+pub struct MusicChannelA;
+pub struct MusicChannelB;
+pub struct UiChannel;
+// same goes for panning, so every sfx plays on a voice channel of its own to be placed around the camera,
+// a one shot sound only changes the tail of the one before it on its voice
+pub struct Voice<const I: usize>;
+
+// channel volumes, from 0 to 1
+pub struct AudioVolume {
+    pub music: f32,
+    pub sfx: f32,
+    pub ui: f32,
+}
+
+impl Default for AudioVolume {
+    fn default() -> Self {
+        Self {
+            music: 0.5,
+            sfx: 0.8,
+            ui: 0.6,
+        }
+    }
+}
+
+// sent by gameplay systems, played once on an sfx voice, quieter and panned the further
+// `position` is from the camera
+pub struct SoundEvent {
+    pub sound: Handle<AudioSource>,
+    pub position: Option<Vec2>,
+}
+
+// loops `sound` on a voice of its own while `emitter` exists, following it around,
+// not played when every looping voice is taken
+pub struct LoopingSoundEvent {
+    pub sound: Handle<AudioSource>,
+    pub emitter: Entity,
+}
+
+enum VoiceCommand {
+    Play { sound: Handle<AudioSource>, looped: bool },
+    Place { volume: f32, panning: f32 },
+    Stop,
+}
+
+// commands for every voice, played by `drive_voice`, and the emitter each looping voice follows
+#[derive(Default)]
+struct Voices {
+    commands: [Vec<VoiceCommand>; VOICES],
+    emitters: [Option<Entity>; LOOP_VOICES],
+    next: usize,
+}
+
+// camera position and half the size of its view, in world units
+struct Listener {
+    position: Vec2,
+    half_view: Vec2,
+}
+
+impl Listener {
+    // volume and panning of a sound at `position`
+    fn hear(&self, position: Vec2) -> (f32, f32) {
+        let width = self.half_view.x.max(1.);
+        let offset = position - self.position;
+        let distance = offset.length() / width;
+        let volume = 1. - ((distance - FULL_VOLUME_DISTANCE) / (SILENT_DISTANCE - FULL_VOLUME_DISTANCE)).clamp(0., 1.);
+        let panning = 0.5 + (offset.x / width).clamp(-1., 1.) * PAN_STRENGTH;
+        (volume, panning)
+    }
+}
+
+// track of the current state and the channel playing it
+#[derive(Default)]
+struct Music {
+    track: Option<Handle<AudioSource>>,
+    // `track` plays on channel b, the other channel has the one fading out
+    on_b: bool,
+    // crossfade progress, from 0 to 1
+    fade: f32,
+}
+
+pub struct GameAudioPlugin;
+
+impl Plugin for GameAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_channel::<MusicChannelA>();
+        app.add_audio_channel::<MusicChannelB>();
+        app.add_audio_channel::<UiChannel>();
+        add_voice::<0>(app);
+        add_voice::<1>(app);
+        add_voice::<2>(app);
+        add_voice::<3>(app);
+        add_voice::<4>(app);
+        add_voice::<5>(app);
+        add_voice::<6>(app);
+        add_voice::<7>(app);
+        app.add_system(apply_volume);
+        app.add_system(switch_music);
+        app.add_system(fade_music.after(switch_music));
+        // after transforms are propagated, so emitters spawned this frame are already placed
+        app.add_system_to_stage(CoreStage::PostUpdate, play_sounds.after(TransformSystem::TransformPropagate));
+        app.add_system_to_stage(CoreStage::PostUpdate, update_emitters.after(play_sounds).label("voices"));
+        app.add_system(button_sounds);
+        app.add_event::<SoundEvent>();
+        app.add_event::<LoopingSoundEvent>();
+        app.init_resource::<AudioVolume>();
+        app.init_resource::<Music>();
+        app.init_resource::<Voices>();
+    }
+}
+
+fn add_voice<const I: usize>(app: &mut App) {
+    app.add_audio_channel::<Voice<I>>();
+    app.add_system_to_stage(CoreStage::PostUpdate, drive_voice::<I>.after("voices"));
+}
+
+// sends the sound when there is one, weapons don't need to have every sound
+pub fn play_sound(
+    sound_events: &mut EventWriter<SoundEvent>,
+    sound: &Option<Handle<AudioSource>>,
+    position: Vec2,
+) {
+    if let Some(sound) = sound {
+        sound_events.send(SoundEvent {
+            sound: sound.clone(),
+            position: Some(position),
+        });
+    }
+}
+
+fn listener(
+    windows: &Windows,
+    q_camera: &Query<(&GlobalTransform, &OrthographicProjection), With<Camera2d>>,
+) -> Option<Listener> {
+    let (tf, projection) = q_camera.get_single().ok()?;
+    let window = windows.get_primary()?;
+    Some(Listener {
+        position: tf.translation().truncate(),
+        half_view: Vec2::new(window.width(), window.height()) / 2. * projection.scale,
+    })
+}
+
+fn apply_volume(
+    volume: Res<AudioVolume>,
+    ui: Res<AudioChannel<UiChannel>>,
+) {
+    if !volume.is_changed() {
+        return;
+    }
+    ui.set_volume(volume.ui);
+}
+
+// every state has its own track, a change of track crossfades
+fn switch_music(
+    app_state: Res<State<AppState>>,
+    audio: Option<Res<AudioAssets>>,
+    channel_a: Res<AudioChannel<MusicChannelA>>,
+    channel_b: Res<AudioChannel<MusicChannelB>>,
+    mut music: ResMut<Music>,
+) {
+    // not loaded yet during the first states
+    let audio = match audio {
+        Some(a) => a,
+        None => return,
+    };
+    if !app_state.is_changed() && !audio.is_added() {
+        return;
+    }
+    let track = match app_state.current() {
+        AppState::Menu => Some(audio.menu_music.clone()),
+        AppState::Game(_) => Some(audio.game_music.clone()),
+        _ => None,
+    };
+    if track == music.track {
+        return;
+    }
+    // the new track goes on the channel of the one before last, cutting it short if it's still fading out
+    music.on_b = !music.on_b;
+    music.fade = 0.;
+    if music.on_b {
+        channel_b.stop();
+        channel_b.set_volume(0.);
+        if let Some(track) = &track {
+            channel_b.play_looped(track.clone());
+        }
+    } else {
+        channel_a.stop();
+        channel_a.set_volume(0.);
+        if let Some(track) = &track {
+            channel_a.play_looped(track.clone());
+        }
+    }
+    music.track = track;
+}
+
+fn fade_music(
+    volume: Res<AudioVolume>,
+    channel_a: Res<AudioChannel<MusicChannelA>>,
+    channel_b: Res<AudioChannel<MusicChannelB>>,
+    mut music: ResMut<Music>,
+    time: Res<Time>,
+) {
+    let fading = music.fade < 1.;
+    if !fading && !volume.is_changed() {
+        return;
+    }
+    music.fade = (music.fade + time.delta_seconds() / CROSSFADE).min(1.);
+    let (new, old) = (volume.music * music.fade, volume.music * (1. - music.fade));
+    let (volume_a, volume_b) = if music.on_b { (old, new) } else { (new, old) };
+    channel_a.set_volume(volume_a);
+    channel_b.set_volume(volume_b);
+    // the old track is silent by now
+    if fading && music.fade >= 1. {
+        if music.on_b {
+            channel_a.stop();
+        } else {
+            channel_b.stop();
+        }
+    }
+}
+
+fn play_sounds(
+    mut sound_events: EventReader<SoundEvent>,
+    mut looping_events: EventReader<LoopingSoundEvent>,
+    mut voices: ResMut<Voices>,
+    volume: Res<AudioVolume>,
+    windows: Res<Windows>,
+    q_camera: Query<(&GlobalTransform, &OrthographicProjection), With<Camera2d>>,
+) {
+    let listener = listener(&windows, &q_camera);
+    for event in sound_events.iter() {
+        let (attenuation, panning) = match (&listener, event.position) {
+            (Some(listener), Some(position)) => listener.hear(position),
+            _ => (1., 0.5),
+        };
+        // too far away to be heard at all
+        if attenuation <= 0. {
+            continue;
+        }
+        let voice = LOOP_VOICES + voices.next;
+        voices.next = (voices.next + 1) % (VOICES - LOOP_VOICES);
+        voices.commands[voice].push(VoiceCommand::Place { volume: attenuation * volume.sfx, panning });
+        voices.commands[voice].push(VoiceCommand::Play { sound: event.sound.clone(), looped: false });
+    }
+    for event in looping_events.iter() {
+        let voice = match voices.emitters.iter().position(|e| e.is_none()) {
+            Some(v) => v,
+            None => continue,
+        };
+        voices.emitters[voice] = Some(event.emitter);
+        // silent until `update_emitters` places it
+        voices.commands[voice].push(VoiceCommand::Place { volume: 0., panning: 0.5 });
+        voices.commands[voice].push(VoiceCommand::Play { sound: event.sound.clone(), looped: true });
+    }
+}
+
+// keeps looping sounds on their emitter, and stops them once it's gone
+fn update_emitters(
+    mut voices: ResMut<Voices>,
+    volume: Res<AudioVolume>,
+    windows: Res<Windows>,
+    q_camera: Query<(&GlobalTransform, &OrthographicProjection), With<Camera2d>>,
+    q_emitter: Query<&GlobalTransform>,
+) {
+    let listener = listener(&windows, &q_camera);
+    for voice in 0..LOOP_VOICES {
+        let emitter = match voices.emitters[voice] {
+            Some(e) => e,
+            None => continue,
+        };
+        let tf = match q_emitter.get(emitter) {
+            Ok(tf) => tf,
+            Err(_) => {
+                voices.emitters[voice] = None;
+                voices.commands[voice].push(VoiceCommand::Stop);
+                continue;
+            }
+        };
+        if let Some(listener) = &listener {
+            let (attenuation, panning) = listener.hear(tf.translation().truncate());
+            voices.commands[voice].push(VoiceCommand::Place { volume: attenuation * volume.sfx, panning });
+        }
+    }
+}
+
+fn drive_voice<const I: usize>(
+    mut voices: ResMut<Voices>,
+    channel: Res<AudioChannel<Voice<I>>>,
+) {
+    for command in voices.commands[I].drain(..) {
+        match command {
+            VoiceCommand::Play { sound, looped } => {
+                if looped {
+                    channel.stop();
+                    channel.play_looped(sound);
+                } else {
+                    channel.play(sound);
+                }
+            }
+            VoiceCommand::Place { volume, panning } => {
+                channel.set_volume(volume);
+                channel.set_panning(panning);
+            }
+            VoiceCommand::Stop => channel.stop(),
+        }
+    }
+}
+
+fn button_sounds(
+    audio: Option<Res<AudioAssets>>,
+    channel: Res<AudioChannel<UiChannel>>,
+    q_interaction: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
+) {
+    let audio = match audio {
+        Some(a) => a,
+        None => return,
+    };
+    for interaction in q_interaction.iter() {
+        if *interaction == Interaction::Clicked {
+            channel.play(audio.click.clone());
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::f32::consts::TAU;
+use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
+use bevy::prelude::*;
+use crate::{AppState, GameState};
+use crate::asset_loader::{AnimationAssets, DataAssets};
+use crate::entity::Team;
+use crate::entity::bullet::{spawn_bullet, Bullet};
+use crate::entity::enemy::{spawn_enemy, Enemy, EnemyType};
+use crate::weapon::WeaponDefinitions;
+
+const BULLETS: usize = 10_000;
+const ENEMIES: usize = 2_000;
+// golden angle, spreads spawns evenly without randomness
+const SPREAD: f32 = 2.399_963;
+
+// stress scene for the collision broadphase, run with `cargo run --release -- --bench`
+// and watch the logged frame time while the bullet and enemy counts are held constant
+pub struct BenchPlugin;
+
+impl Plugin for BenchPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(FrameTimeDiagnosticsPlugin);
+        app.add_plugin(LogDiagnosticsPlugin::default());
+        app.add_system_set(SystemSet::on_update(AppState::Game(GameState::Playing))
+            .with_system(keep_population.before("movement"))
+        );
+    }
+}
+
+fn keep_population(
+    mut commands: Commands,
+    animations: Res<AnimationAssets>,
+    data: Res<DataAssets>,
+    weapon_defs: Res<Assets<WeaponDefinitions>>,
+    q_bullet: Query<(), With<Bullet>>,
+    q_enemy: Query<(), With<Enemy>>,
+    mut spawned: Local<u32>,
+) {
+    let weapon = match weapon_defs.get(&data.weapons).and_then(|w| w.weapons.first()) {
+        Some(w) => w,
+        None => return,
+    };
+    let bullets = q_bullet.iter().count();
+    for _ in bullets..BULLETS {
+        let (position, angle) = spiral(&mut spawned, 400.);
+        spawn_bullet(&mut commands, weapon, position.extend(0.), Vec2::new(angle.cos(), angle.sin()), Team::Player);
+    }
+    let enemies = q_enemy.iter().count();
+    for _ in enemies..ENEMIES {
+        let (position, _) = spiral(&mut spawned, 800.);
+        spawn_enemy(&mut commands, &animations, EnemyType::Zombie, position);
+    }
+}
+
+fn spiral(n: &mut u32, max_radius: f32) -> (Vec2, f32) {
+    *n = n.wrapping_add(1);
+    let angle = (*n as f32 * SPREAD) % TAU;
+    let radius = (*n % 997) as f32 / 997. * max_radius;
+    (Vec2::new(angle.cos(), angle.sin()) * radius, angle)
+}
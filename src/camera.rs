@@ -1,6 +1,69 @@
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
 use bevy::prelude::*;
 use crate::{AppState, GameState};
-use crate::entity::player::Player;
+use crate::asset_loader::DataAssets;
+use crate::entity::ShotEvent;
+use crate::entity::player::{Aim, Player};
+use crate::input::Action;
+use crate::level::{CurrentLevel, Level};
+use crate::weapon::WeaponDefinitions;
+
+// offset in world units and rotation in radians of a full shake
+const MAX_SHAKE_OFFSET: f32 = 4.;
+const MAX_SHAKE_ANGLE: f32 = 0.04;
+// trauma lost per second
+const TRAUMA_DECAY: f32 = 1.8;
+// how fast the camera wobbles while shaking
+const SHAKE_SPEED: f32 = 30.;
+// pixels of a touchpad scroll that count as one mouse wheel line
+const PIXELS_PER_LINE: f32 = 100.;
+
+// how the camera follows the player
+pub struct CameraSettings {
+    // world units per screen pixel, smaller is closer, changed by the zoom actions
+    pub zoom: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    // factor the zoom changes by per zoom action or wheel line
+    pub zoom_step: f32,
+    // how quickly the camera catches up with the player, higher is snappier
+    pub smoothing: f32,
+    // half the size of the box around the view center the player moves in without dragging the camera
+    pub dead_zone: Vec2,
+    // how far ahead of the player the camera looks in the aim direction
+    pub look_ahead: f32,
+    // keeps the view from showing what's outside the level
+    pub clamp_to_level: bool,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            zoom: 0.2,
+            min_zoom: 0.1,
+            max_zoom: 0.4,
+            zoom_step: 1.15,
+            smoothing: 8.,
+            dead_zone: Vec2::new(12., 8.),
+            look_ahead: 24.,
+            clamp_to_level: true,
+        }
+    }
+}
+
+// center of the view before the shake, none until there is a player to look at
+#[derive(Default)]
+struct CameraRig(Option<Vec2>);
+
+// how hard the camera shakes, from 0 to 1, the shake grows with its square so small hits stay subtle
+#[derive(Default)]
+pub struct Trauma(f32);
+
+impl Trauma {
+    pub fn add(&mut self, amount: f32) {
+        self.0 = (self.0 + amount).clamp(0., 1.);
+    }
+}
 
 pub(crate) struct CameraPlugin;
 
@@ -8,29 +71,179 @@ impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_system_set(SystemSet::on_update(AppState::Game(GameState::Playing))
-                .with_system(camera_follow_player.after("movement"))
+                .with_system(zoom_camera.before(camera_follow_player))
+                .with_system(camera_follow_player.after("movement").after("collision").label("camera"))
+                .with_system(shots_shake.after("shoot"))
+                // its own label, aiming goes after "camera" and has to see the camera before it shakes
+                .with_system(shake_camera.after(camera_follow_player).after(shots_shake).label("shake"))
             );
+        app.add_system_set(SystemSet::on_enter(AppState::Menu)
+            .with_system(reset_camera)
+        );
+        app.init_resource::<CameraSettings>();
+        app.init_resource::<CameraRig>();
+        app.init_resource::<Trauma>();
     }
 }
 
-#[allow(clippy::type_complexity)]
+// eases the projection towards `CameraSettings::zoom`
+fn zoom_camera(
+    mut settings: ResMut<CameraSettings>,
+    mut wheel_events: EventReader<MouseWheel>,
+    actions: Res<Input<Action>>,
+    time: Res<Time>,
+    mut q_camera: Query<&mut OrthographicProjection, With<Camera2d>>,
+) {
+    let mut steps = actions.just_pressed(Action::ZoomOut) as i8 as f32 - actions.just_pressed(Action::ZoomIn) as i8 as f32;
+    for event in wheel_events.iter() {
+        // scrolling up zooms in
+        steps -= match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_LINE,
+        };
+    }
+    if steps != 0. {
+        settings.zoom = (settings.zoom * settings.zoom_step.powf(steps)).clamp(settings.min_zoom, settings.max_zoom);
+    }
+    let mut projection = match q_camera.get_single_mut() {
+        Ok(p) => p,
+        Err(_) => return,
+    };
+    if projection.scale != settings.zoom {
+        let t = 1. - (-settings.smoothing * time.delta_seconds()).exp();
+        projection.scale += (settings.zoom - projection.scale) * t;
+        // close enough, stops touching the projection every frame
+        if (projection.scale - settings.zoom).abs() < 0.0001 {
+            projection.scale = settings.zoom;
+        }
+    }
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 fn camera_follow_player(
-    mut q_camera_player: ParamSet<(
-        Query<&mut Transform, With<Camera2d>>,
-        Query<&Transform, With<Player>>
-    )>
+    settings: Res<CameraSettings>,
+    mut rig: ResMut<CameraRig>,
+    current: Res<CurrentLevel>,
+    levels: Res<Assets<Level>>,
+    windows: Res<Windows>,
+    time: Res<Time>,
+    q_player: Query<(&Transform, &Aim), With<Player>>,
+    mut q_camera: Query<(&mut Transform, &OrthographicProjection), (With<Camera2d>, Without<Player>)>,
+) {
+    let (mut camera, projection) = match q_camera.get_single_mut() {
+        Ok(c) => c,
+        Err(_) => return,
+    };
+    // without a player the camera stays where it was
+    if let Ok((player, aim)) = q_player.get_single() {
+        let target = player.translation.truncate() + aim.0 * settings.look_ahead;
+        let position = match rig.0 {
+            Some(position) => {
+                // only the part of the target outside of the dead zone drags the camera along
+                let offset = target - position;
+                let outside = offset - offset.clamp(-settings.dead_zone, settings.dead_zone);
+                // exponential so it's the same at any frame rate
+                let t = 1. - (-settings.smoothing * time.delta_seconds()).exp();
+                position + outside * t
+            }
+            // first frame of a game jumps right to the player
+            None => target,
+        };
+        let level = levels.get(&current.0).filter(|_| settings.clamp_to_level);
+        let position = match (level, windows.get_primary()) {
+            (Some(level), Some(window)) => {
+                let half_view = Vec2::new(window.width(), window.height()) / 2. * projection.scale;
+                let min = level.origin();
+                let max = min + level.size.as_vec2() * level.tile_size;
+                clamp_view(position, half_view, min, max)
+            }
+            _ => position,
+        };
+        rig.0 = Some(position);
+    }
+    if let Some(position) = rig.0 {
+        camera.translation.x = position.x;
+        camera.translation.y = position.y;
+    }
+}
+
+// keeps a view of `half_view` around `center` inside `min` and `max`, centered on an axis the view is bigger on
+fn clamp_view(center: Vec2, half_view: Vec2, min: Vec2, max: Vec2) -> Vec2 {
+    let axis = |c: f32, half: f32, min: f32, max: f32| {
+        if max - min <= half * 2. {
+            (min + max) / 2.
+        } else {
+            c.clamp(min + half, max - half)
+        }
+    };
+    Vec2::new(
+        axis(center.x, half_view.x, min.x, max.x),
+        axis(center.y, half_view.y, min.y, max.y),
+    )
+}
+
+fn shots_shake(
+    mut shot_events: EventReader<ShotEvent>,
+    mut trauma: ResMut<Trauma>,
+    data: Res<DataAssets>,
+    weapon_defs: Res<Assets<WeaponDefinitions>>,
+) {
+    let weapons = match weapon_defs.get(&data.weapons) {
+        Some(w) => w,
+        None => return,
+    };
+    for event in shot_events.iter() {
+        if let Some(weapon) = weapons.get(&event.weapon) {
+            trauma.add(weapon.recoil.shake);
+        }
+    }
+}
+
+// on top of where `camera_follow_player` put the camera
+fn shake_camera(
+    mut trauma: ResMut<Trauma>,
+    time: Res<Time>,
+    mut q_camera: Query<&mut Transform, With<Camera2d>>,
 ) {
-    let player = q_camera_player.p1();
-    if let Ok(player) = player.get_single() {
-        let (player_x, player_y) = (player.translation.x, player.translation.y);
-        let mut q_cam = q_camera_player.p0();
-        let mut camera = q_cam.single_mut();
-        camera.translation.x = player_x;
-        camera.translation.y = player_y;
-    } else {
-        let mut q_cam = q_camera_player.p0();
-        let mut camera = q_cam.single_mut();
-        camera.translation.x = 0.;
-        camera.translation.y = 0.;
+    let mut camera = match q_camera.get_single_mut() {
+        Ok(c) => c,
+        Err(_) => return,
+    };
+    let shake = trauma.0 * trauma.0;
+    let t = time.seconds_since_startup() as f32 * SHAKE_SPEED;
+    camera.translation.x += wobble(t, 0.) * shake * MAX_SHAKE_OFFSET;
+    camera.translation.y += wobble(t, 1.7) * shake * MAX_SHAKE_OFFSET;
+    camera.rotation = Quat::from_rotation_z(wobble(t, 3.1) * shake * MAX_SHAKE_ANGLE);
+    if trauma.0 > 0. {
+        trauma.add(-TRAUMA_DECAY * time.delta_seconds());
     }
+}
+
+// smooth value between -1 and 1, `seed` keeps the axes apart
+fn wobble(t: f32, seed: f32) -> f32 {
+    ((t + seed * 10.).sin() + (t * 2.3 + seed).sin() * 0.5) / 1.5
+}
+
+// the next game starts without shake, right on the player
+fn reset_camera(
+    mut rig: ResMut<CameraRig>,
+    mut trauma: ResMut<Trauma>,
+    mut q_camera: Query<&mut Transform, With<Camera2d>>,
+) {
+    rig.0 = None;
+    trauma.0 = 0.;
+    for mut camera in q_camera.iter_mut() {
+        camera.rotation = Quat::IDENTITY;
+    }
+}
+
+// window cursor position to world position, accounting for the camera's position and zoom
+pub fn cursor_to_world(
+    window: &Window,
+    camera: &Transform,
+    projection: &OrthographicProjection,
+) -> Option<Vec2> {
+    let cursor = window.cursor_position()?;
+    let size = Vec2::new(window.width(), window.height());
+    Some(camera.translation.truncate() + (cursor - size / 2.) * projection.scale)
 }
\ No newline at end of file
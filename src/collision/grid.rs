@@ -0,0 +1,206 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use crate::collision::Shape;
+
+// uniform grid over every collider, rebuilt each tick in `rebuild_grid`
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<(Entity, Shape)>>,
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        Self::new(32.)
+    }
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::default(),
+        }
+    }
+
+    pub fn cell(&self, point: Vec2) -> IVec2 {
+        (point / self.cell_size).floor().as_ivec2()
+    }
+
+    // empties the grid, cells that were used last tick keep their allocation
+    pub fn clear(&mut self) {
+        self.cells.retain(|_, cell| {
+            let used = !cell.is_empty();
+            cell.clear();
+            used
+        });
+    }
+
+    pub fn insert(&mut self, entity: Entity, shape: Shape) {
+        let half = shape.half_extents();
+        let min = self.cell(shape.center() - half);
+        let max = self.cell(shape.center() + half);
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                self.cells.entry(IVec2::new(x, y)).or_default().push((entity, shape));
+            }
+        }
+    }
+
+    // every collider whose cells overlap the box, each reported once
+    pub fn query_aabb(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = (Entity, Shape)> + '_ {
+        let (c_min, c_max) = (self.cell(min), self.cell(max));
+        (c_min.y..=c_max.y)
+            .flat_map(move |y| (c_min.x..=c_max.x).map(move |x| IVec2::new(x, y)))
+            .filter_map(move |c| self.cells.get(&c).map(|entries| (c, entries)))
+            .flat_map(move |(c, entries)| {
+                entries.iter()
+                    // a collider spanning several cells is only reported from the first shared one
+                    .filter(move |(_, shape)| {
+                        self.cell(shape.center() - shape.half_extents()).max(c_min) == c
+                    })
+                    .copied()
+            })
+    }
+
+    pub fn neighbours(&self, point: Vec2, radius: f32) -> impl Iterator<Item = (Entity, Shape)> + '_ {
+        self.query_aabb(point - Vec2::splat(radius), point + Vec2::splat(radius))
+            .filter(move |(_, shape)| {
+                let closest = point.clamp(
+                    shape.center() - shape.half_extents(),
+                    shape.center() + shape.half_extents(),
+                );
+                closest.distance_squared(point) <= radius * radius
+            })
+    }
+
+    // nearest collider hit by the segment `from` -> `to`, walks the cells along the segment
+    pub fn ray_cast(
+        &self,
+        from: Vec2,
+        to: Vec2,
+        mut filter: impl FnMut(Entity) -> bool,
+    ) -> Option<(Entity, f32)> {
+        let dir = to - from;
+        let mut cell = self.cell(from);
+        let end = self.cell(to);
+        let step = IVec2::new(axis_step(dir.x), axis_step(dir.y));
+        let boundary = |c: i32, s: i32| (c + (s > 0) as i32) as f32 * self.cell_size;
+        let mut t_max = Vec2::new(
+            if step.x != 0 { (boundary(cell.x, step.x) - from.x) / dir.x } else { f32::INFINITY },
+            if step.y != 0 { (boundary(cell.y, step.y) - from.y) / dir.y } else { f32::INFINITY },
+        );
+        let t_delta = Vec2::new(
+            if step.x != 0 { self.cell_size / dir.x.abs() } else { f32::INFINITY },
+            if step.y != 0 { self.cell_size / dir.y.abs() } else { f32::INFINITY },
+        );
+        let mut best: Option<(Entity, f32)> = None;
+        loop {
+            if let Some(entries) = self.cells.get(&cell) {
+                for (entity, shape) in entries.iter() {
+                    if !filter(*entity) {
+                        continue;
+                    }
+                    if let Some(t) = shape.ray_cast(from, to) {
+                        if best.is_none_or(|(_, best_t)| t < best_t) {
+                            best = Some((*entity, t));
+                        }
+                    }
+                }
+            }
+            let t_exit = t_max.x.min(t_max.y);
+            // nothing in later cells can be closer
+            if matches!(best, Some((_, t)) if t <= t_exit) {
+                break;
+            }
+            if cell == end || t_exit > 1. {
+                break;
+            }
+            if t_max.x < t_max.y {
+                cell.x += step.x;
+                t_max.x += t_delta.x;
+            } else {
+                cell.y += step.y;
+                t_max.y += t_delta.y;
+            }
+        }
+        best
+    }
+}
+
+fn axis_step(d: f32) -> i32 {
+    if d > 0. {
+        1
+    } else if d < 0. {
+        -1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aabb(x: f32, y: f32, half: f32) -> Shape {
+        Shape::Aabb { center: Vec2::new(x, y), half: Vec2::splat(half) }
+    }
+
+    #[test]
+    fn query_aabb_reports_spanning_colliders_once() {
+        let mut grid = SpatialGrid::new(32.);
+        // covers cells 0..=2 on both axes
+        grid.insert(Entity::from_raw(1), aabb(48., 48., 40.));
+        grid.insert(Entity::from_raw(2), aabb(10., 10., 2.));
+        let mut found: Vec<u32> = grid.query_aabb(Vec2::splat(-100.), Vec2::splat(200.))
+            .map(|(e, _)| e.id())
+            .collect();
+        found.sort();
+        assert_eq!(found, vec![1, 2]);
+        // a box only touching the far corner of the big collider still finds it, once
+        let found: Vec<u32> = grid.query_aabb(Vec2::splat(70.), Vec2::splat(80.))
+            .map(|(e, _)| e.id())
+            .collect();
+        assert_eq!(found, vec![1]);
+    }
+
+    #[test]
+    fn ray_cast_hits_nearest_in_both_directions() {
+        let mut grid = SpatialGrid::new(32.);
+        grid.insert(Entity::from_raw(1), aabb(50., 0., 5.));
+        grid.insert(Entity::from_raw(2), aabb(150., 0., 5.));
+        let (hit, t) = grid.ray_cast(Vec2::ZERO, Vec2::new(200., 0.), |_| true).unwrap();
+        assert_eq!(hit.id(), 1);
+        assert!((t - 45. / 200.).abs() < 1e-4);
+        let (hit, _) = grid.ray_cast(Vec2::new(200., 0.), Vec2::ZERO, |_| true).unwrap();
+        assert_eq!(hit.id(), 2);
+    }
+
+    #[test]
+    fn ray_cast_skips_filtered_and_misses() {
+        let mut grid = SpatialGrid::new(32.);
+        grid.insert(Entity::from_raw(1), aabb(50., 0., 5.));
+        grid.insert(Entity::from_raw(2), aabb(150., 0., 5.));
+        let (hit, _) = grid.ray_cast(Vec2::ZERO, Vec2::new(200., 0.), |e| e.id() != 1).unwrap();
+        assert_eq!(hit.id(), 2);
+        // passes above both
+        assert!(grid.ray_cast(Vec2::new(0., 20.), Vec2::new(200., 20.), |_| true).is_none());
+        // stops short of the first
+        assert!(grid.ray_cast(Vec2::ZERO, Vec2::new(40., 0.), |_| true).is_none());
+    }
+
+    #[test]
+    fn circles_are_found_and_hit_by_their_outline() {
+        let mut grid = SpatialGrid::new(32.);
+        let circle = Shape::Circle { center: Vec2::new(100., 0.), radius: 10. };
+        grid.insert(Entity::from_raw(1), circle);
+        let found: Vec<u32> = grid.query_aabb(Vec2::new(85., -5.), Vec2::new(95., 5.))
+            .map(|(e, _)| e.id())
+            .collect();
+        assert_eq!(found, vec![1]);
+        let (hit, t) = grid.ray_cast(Vec2::ZERO, Vec2::new(200., 0.), |_| true).unwrap();
+        assert_eq!(hit.id(), 1);
+        assert!((t - 90. / 200.).abs() < 1e-4);
+        // through the corner of its bounding box but past the circle
+        assert!(grid.ray_cast(Vec2::new(98., 20.), Vec2::new(120., -2.), |_| true).is_none());
+    }
+}
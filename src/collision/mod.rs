@@ -0,0 +1,269 @@
+pub(crate) mod grid;
+
+use bevy::prelude::*;
+use crate::{AppState, GameState};
+use crate::collision::grid::SpatialGrid;
+use crate::asset_loader::DataAssets;
+use crate::entity::{GameEntity, Hitbox, Motion, Team};
+use crate::entity::bullet::{Bullet, Shooter};
+use crate::weapon::WeaponDefinitions;
+
+pub struct CollisionPlugin;
+
+impl Plugin for CollisionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(SystemSet::on_update(AppState::Game(GameState::Playing))
+                .with_system(rebuild_grid.label("broadphase").after("movement"))
+                .with_system(bullet_collision.label("collision").after("broadphase"))
+                .with_system(entity_collision.label("collision").after("broadphase"))
+            );
+        app.init_resource::<SpatialGrid>();
+        app.add_event::<BulletHitEvent>();
+        app.add_event::<ContactEvent>();
+    }
+}
+
+// static collider, entities get pushed out of it and bullets stop on it
+#[derive(Component)]
+pub struct Solid;
+
+// where the entity was last frame, bullets are swept from here to their current position
+#[derive(Component)]
+pub struct PreviousPosition(pub Vec2);
+
+pub struct BulletHitEvent {
+    pub bullet: Entity,
+    pub target: Entity,
+    pub point: Vec2,
+}
+
+// two overlapping entities, sent after they were pushed apart
+pub struct ContactEvent {
+    pub a: Entity,
+    pub b: Entity,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Shape {
+    Aabb { center: Vec2, half: Vec2 },
+    Circle { center: Vec2, radius: f32 },
+}
+
+impl Hitbox {
+    pub fn shape(&self, position: Vec2) -> Shape {
+        match *self {
+            Hitbox::Aabb(size) => Shape::Aabb { center: position, half: size / 2. },
+            Hitbox::Circle(radius) => Shape::Circle { center: position, radius },
+        }
+    }
+}
+
+impl Shape {
+    pub fn center(&self) -> Vec2 {
+        match *self {
+            Shape::Aabb { center, .. } => center,
+            Shape::Circle { center, .. } => center,
+        }
+    }
+
+    pub fn half_extents(&self) -> Vec2 {
+        match *self {
+            Shape::Aabb { half, .. } => half,
+            Shape::Circle { radius, .. } => Vec2::splat(radius),
+        }
+    }
+
+    // vector by which self has to move to stop overlapping other
+    pub fn overlap(&self, other: &Shape) -> Option<Vec2> {
+        match (*self, *other) {
+            (Shape::Aabb { center: ca, half: ha }, Shape::Aabb { center: cb, half: hb }) => {
+                aabb_aabb(ca, ha, cb, hb)
+            }
+            (Shape::Circle { center: ca, radius: ra }, Shape::Circle { center: cb, radius: rb }) => {
+                let d = cb - ca;
+                let dist = d.length();
+                let r = ra + rb;
+                if dist >= r {
+                    return None;
+                }
+                let normal = if dist > f32::EPSILON { d / dist } else { Vec2::X };
+                Some(-normal * (r - dist))
+            }
+            (Shape::Aabb { center, half }, Shape::Circle { center: cc, radius }) => {
+                aabb_circle(center, half, cc, radius)
+            }
+            (Shape::Circle { center: cc, radius }, Shape::Aabb { center, half }) => {
+                aabb_circle(center, half, cc, radius).map(|push| -push)
+            }
+        }
+    }
+
+    // first point of the segment `from` -> `to` inside the shape, as a fraction of the segment
+    pub fn ray_cast(&self, from: Vec2, to: Vec2) -> Option<f32> {
+        let dir = to - from;
+        match *self {
+            Shape::Aabb { center, half } => {
+                let (min, max) = (center - half, center + half);
+                let mut t_min = 0f32;
+                let mut t_max = 1f32;
+                for axis in 0..2 {
+                    if dir[axis].abs() < f32::EPSILON {
+                        if from[axis] < min[axis] || from[axis] > max[axis] {
+                            return None;
+                        }
+                        continue;
+                    }
+                    let t1 = (min[axis] - from[axis]) / dir[axis];
+                    let t2 = (max[axis] - from[axis]) / dir[axis];
+                    t_min = t_min.max(t1.min(t2));
+                    t_max = t_max.min(t1.max(t2));
+                    if t_min > t_max {
+                        return None;
+                    }
+                }
+                Some(t_min)
+            }
+            Shape::Circle { center, radius } => {
+                let m = from - center;
+                let c = m.dot(m) - radius * radius;
+                if c <= 0. {
+                    return Some(0.);
+                }
+                let a = dir.dot(dir);
+                if a < f32::EPSILON {
+                    return None;
+                }
+                let b = m.dot(dir);
+                let disc = b * b - a * c;
+                if disc < 0. {
+                    return None;
+                }
+                let t = (-b - disc.sqrt()) / a;
+                (0. ..=1.).contains(&t).then_some(t)
+            }
+        }
+    }
+}
+
+fn aabb_aabb(ca: Vec2, ha: Vec2, cb: Vec2, hb: Vec2) -> Option<Vec2> {
+    let d = cb - ca;
+    let px = ha.x + hb.x - d.x.abs();
+    let py = ha.y + hb.y - d.y.abs();
+    if px <= 0. || py <= 0. {
+        return None;
+    }
+    if px < py {
+        Some(Vec2::new(-px * d.x.signum(), 0.))
+    } else {
+        Some(Vec2::new(0., -py * d.y.signum()))
+    }
+}
+
+// push for the aabb
+fn aabb_circle(center: Vec2, half: Vec2, cc: Vec2, radius: f32) -> Option<Vec2> {
+    let closest = cc.clamp(center - half, center + half);
+    let d = cc - closest;
+    let dist = d.length();
+    if dist >= radius {
+        return None;
+    }
+    if dist <= f32::EPSILON {
+        // circle center is inside the box, treat the circle as a box
+        return aabb_aabb(center, half, cc, Vec2::splat(radius));
+    }
+    Some(-d / dist * (radius - dist))
+}
+
+#[allow(clippy::type_complexity)]
+fn rebuild_grid(
+    mut grid: ResMut<SpatialGrid>,
+    q_collider: Query<(Entity, &Transform, &Hitbox), Or<(With<GameEntity>, With<Solid>)>>,
+) {
+    grid.clear();
+    for (ent, tf, hitbox) in q_collider.iter() {
+        grid.insert(ent, hitbox.shape(tf.translation.truncate()));
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn bullet_collision(
+    grid: Res<SpatialGrid>,
+    mut hit_events: EventWriter<BulletHitEvent>,
+    data: Res<DataAssets>,
+    weapon_defs: Res<Assets<WeaponDefinitions>>,
+    mut q_bullet: Query<(Entity, &Transform, &mut PreviousPosition, &Team, Option<&Shooter>), With<Bullet>>,
+    q_team: Query<&Team>,
+) {
+    let friendly_fire = weapon_defs.get(&data.weapons).map(|w| w.friendly_fire).unwrap_or_default();
+    for (bullet, tf, mut prev, team, shooter) in q_bullet.iter_mut() {
+        let from = prev.0;
+        let to = tf.translation.truncate();
+        // the shooter and, without friendly fire, its own side get passed through
+        let hits = |ent: Entity| {
+            shooter.is_none_or(|s| s.0 != ent)
+                && q_team.get(ent).map_or(true, |t| t != team || friendly_fire.allows(*team))
+        };
+        if let Some((target, t)) = grid.ray_cast(from, to, hits) {
+            hit_events.send(BulletHitEvent {
+                bullet,
+                target,
+                point: from.lerp(to, t),
+            });
+        }
+        prev.0 = to;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn entity_collision(
+    grid: Res<SpatialGrid>,
+    mut contact_events: EventWriter<ContactEvent>,
+    mut q_entity: Query<(Entity, &mut Transform, &Hitbox, Option<&mut Motion>), (With<GameEntity>, Without<Solid>)>,
+    q_solid: Query<(), With<Solid>>,
+) {
+    // entity vs entity, both get pushed by half of the overlap
+    let mut pushes = Vec::new();
+    for (ent, tf, hitbox, _) in q_entity.iter() {
+        let shape = hitbox.shape(tf.translation.truncate());
+        let half = shape.half_extents();
+        for (other, other_shape) in grid.query_aabb(shape.center() - half, shape.center() + half) {
+            // every pair is handled once, from its lower entity
+            if other <= ent || q_solid.get(other).is_ok() {
+                continue;
+            }
+            if let Some(push) = shape.overlap(&other_shape) {
+                pushes.push((ent, push / 2.));
+                pushes.push((other, -push / 2.));
+                contact_events.send(ContactEvent { a: ent, b: other });
+            }
+        }
+    }
+    for (ent, push) in pushes {
+        if let Ok((_, mut tf, _, _)) = q_entity.get_mut(ent) {
+            tf.translation += push.extend(0.);
+        }
+    }
+    // entity vs wall, only the entity moves, walls are resolved one by one so corners don't double push
+    for (_, mut tf, hitbox, mut motion) in q_entity.iter_mut() {
+        let shape = hitbox.shape(tf.translation.truncate());
+        let half = shape.half_extents();
+        for (solid, solid_shape) in grid.query_aabb(shape.center() - half, shape.center() + half) {
+            if q_solid.get(solid).is_err() {
+                continue;
+            }
+            let shape = hitbox.shape(tf.translation.truncate());
+            if let Some(push) = shape.overlap(&solid_shape) {
+                tf.translation += push.extend(0.);
+                // stop moving into the wall
+                if let Some(motion) = motion.as_mut() {
+                    let normal = push.normalize_or_zero();
+                    let into = motion.speed.dot(normal);
+                    if into < 0. {
+                        motion.speed -= normal * into;
+                    }
+                }
+            }
+        }
+    }
+}
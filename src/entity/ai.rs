@@ -0,0 +1,222 @@
+use bevy::prelude::*;
+use rand::Rng;
+use crate::{AppState, GameState};
+use crate::entity::{Health, Motion, NoiseEvent};
+use crate::entity::enemy::Enemy;
+use crate::entity::player::Player;
+use crate::navigation::{next_waypoint, FlowField, NavGrid, PathFollower};
+
+// distance to the player at which chasing turns into attacking
+const ATTACK_RANGE: f32 = 16.;
+// how far a wander target can be
+const WANDER_DISTANCE: f32 = 60.;
+// fraction of the max speed used while not after the player
+const CALM_SPEED: f32 = 0.4;
+// seconds an enemy keeps chasing after losing sight, following the flow field around walls
+const CHASE_MEMORY: f32 = 3.;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AiState {
+    // standing around until `timer` runs out
+    Idle,
+    // walking to a random spot nearby
+    Wander(Vec2),
+    // going to where the player was last seen or heard, then looking around
+    Investigate(Vec2),
+    // the player is in sight, or was until `CHASE_MEMORY` ago
+    Chase,
+    // the player is within `ATTACK_RANGE`
+    Attack,
+    // hurt too badly, running from the player until `timer` runs out
+    Flee,
+}
+
+#[derive(Component)]
+pub struct Ai {
+    pub state: AiState,
+    pub timer: Timer,
+    // A* path of `Investigate`, next waypoint last
+    pub path: Vec<Vec2>,
+    pub last_seen: Option<Vec2>,
+}
+
+impl Default for Ai {
+    fn default() -> Self {
+        Self {
+            state: AiState::Idle,
+            timer: Timer::from_seconds(idle_time(), false),
+            path: Vec::new(),
+            last_seen: None,
+        }
+    }
+}
+
+impl Ai {
+    fn set(&mut self, state: AiState, seconds: f32) {
+        self.state = state;
+        self.timer = Timer::from_seconds(seconds, false);
+        self.path.clear();
+    }
+}
+
+fn idle_time() -> f32 {
+    rand::thread_rng().gen_range(1.0..3.0)
+}
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(SystemSet::on_update(AppState::Game(GameState::Playing))
+                .with_system(update_ai.label("ai").after("navigation").after("shoot"))
+                .with_system(ai_movement.after("ai").before("movement"))
+            );
+    }
+}
+
+// perception and state changes
+fn update_ai(
+    mut noise_events: EventReader<NoiseEvent>,
+    mut q_enemy: Query<(&Transform, &Enemy, &Health, &mut Ai)>,
+    q_player: Query<&Transform, With<Player>>,
+    grid: Res<NavGrid>,
+    time: Res<Time>,
+) {
+    let player = q_player.get_single().ok().map(|tf| tf.translation.truncate());
+    let noises: Vec<&NoiseEvent> = noise_events.iter().collect();
+    for (tf, enemy, health, mut ai) in q_enemy.iter_mut() {
+        let stats = enemy.0.stats();
+        let position = tf.translation.truncate();
+        ai.timer.tick(time.delta());
+        // walls block sight but not sound
+        let seen = player.filter(|p| {
+            p.distance(position) <= stats.sight && grid.line_of_sight(position, *p)
+        });
+        let heard = noises.iter()
+            .filter(|n| n.position.distance(position) <= n.radius)
+            .map(|n| n.position)
+            .next_back();
+        if let Some(p) = seen {
+            ai.last_seen = Some(p);
+        }
+        let fleeing = (health.current as f32) < health.max as f32 * stats.flee_health;
+        if ai.state == AiState::Flee {
+            if ai.timer.finished() {
+                ai.set(AiState::Idle, idle_time());
+            }
+            continue;
+        }
+        if fleeing && seen.is_some() {
+            ai.set(AiState::Flee, 3.);
+            continue;
+        }
+        if let Some(p) = seen {
+            let state = if p.distance(position) <= ATTACK_RANGE { AiState::Attack } else { AiState::Chase };
+            ai.set(state, CHASE_MEMORY);
+            continue;
+        }
+        if matches!(ai.state, AiState::Chase | AiState::Attack) {
+            if ai.timer.finished() {
+                // lost track, go to where the player was last seen
+                let target = ai.last_seen.unwrap_or(position);
+                investigate(&mut ai, &grid, position, target);
+            } else {
+                ai.state = AiState::Chase;
+            }
+            continue;
+        }
+        if let Some(target) = heard {
+            // automatic fire keeps making noise, only a new spot is worth a new path
+            let same_spot = matches!(ai.state, AiState::Investigate(t) if t.distance(target) < grid.tile_size() * 2.);
+            if !same_spot {
+                investigate(&mut ai, &grid, position, target);
+            }
+            continue;
+        }
+        match ai.state {
+            AiState::Idle if ai.timer.finished() => {
+                let mut rng = rand::thread_rng();
+                let offset = Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)) * WANDER_DISTANCE;
+                let target = position + offset;
+                if grid.line_of_sight(position, target) {
+                    ai.set(AiState::Wander(target), 4.);
+                } else {
+                    ai.set(AiState::Idle, idle_time());
+                }
+            }
+            AiState::Wander(target) if target.distance(position) < 4. || ai.timer.finished() => {
+                ai.set(AiState::Idle, idle_time());
+            }
+            // the timer only starts counting down once the spot is reached, see `ai_movement`
+            AiState::Investigate(_) if ai.timer.finished() => {
+                ai.set(AiState::Idle, idle_time());
+            }
+            _ => {}
+        }
+    }
+}
+
+fn investigate(ai: &mut Ai, grid: &NavGrid, position: Vec2, target: Vec2) {
+    ai.set(AiState::Investigate(target), 2.);
+    ai.path = grid.find_path(position, target).unwrap_or_default();
+}
+
+// turns the state into movement through `Motion`
+#[allow(clippy::type_complexity)]
+fn ai_movement(
+    mut q_enemy: Query<(&Transform, &mut Motion, &Enemy, &mut TextureAtlasSprite, &mut Ai, Option<&mut PathFollower>)>,
+    q_player: Query<&Transform, With<Player>>,
+    grid: Res<NavGrid>,
+    flow: Res<FlowField>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds() * 100.0;
+    let player = q_player.get_single().ok().map(|tf| tf.translation.truncate());
+    for (tf, mut motion, enemy, mut sprite, mut ai, follower) in q_enemy.iter_mut() {
+        let stats = enemy.0.stats();
+        let position = tf.translation.truncate();
+        let reach = grid.tile_size() / 3.;
+        let (direction, speed) = match (ai.state, player) {
+            // ranged enemies hold their ground once the player is close enough to shoot at
+            (AiState::Chase | AiState::Attack, Some(player))
+                if player.distance(position) <= stats.keep_distance && grid.line_of_sight(position, player) => {
+                sprite.flip_x = player.x < position.x;
+                (None, 0.)
+            }
+            (AiState::Chase | AiState::Attack, Some(player)) => {
+                // straight at the player when nothing is in the way, around the walls otherwise
+                let steer = if grid.line_of_sight(position, player) {
+                    None
+                } else if let Some(mut follower) = follower {
+                    if follower.repath.tick(time.delta()).just_finished() {
+                        follower.path = grid.find_path(position, player).unwrap_or_default();
+                    }
+                    follower.next(position, reach).map(|w| (w - position).normalize_or_zero())
+                } else {
+                    flow.direction(&grid, position)
+                };
+                (steer.or_else(|| (player - position).try_normalize()), stats.speed)
+            }
+            (AiState::Flee, Some(player)) => ((position - player).try_normalize(), stats.speed),
+            (AiState::Wander(target), _) => ((target - position).try_normalize(), stats.speed * CALM_SPEED),
+            (AiState::Investigate(target), _) => {
+                let waypoint = next_waypoint(&mut ai.path, position, reach)
+                    .or_else(|| (target.distance(position) > reach).then_some(target));
+                match waypoint {
+                    Some(w) => {
+                        // keeps the look around timer full until the spot is reached
+                        ai.timer.reset();
+                        ((w - position).try_normalize(), stats.speed * CALM_SPEED * 2.)
+                    }
+                    None => (None, 0.),
+                }
+            }
+            _ => (None, 0.),
+        };
+        motion.steer(direction, speed, delta);
+        if let Some(dir) = direction {
+            sprite.flip_x = dir.x < 0.;
+        }
+    }
+}
@@ -0,0 +1,457 @@
+use std::f32::consts::TAU;
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+use crate::{AppState, GameState};
+use crate::animation::{spawn_corpse, Animator, IDLE};
+use crate::asset_loader::{AnimationAssets, AudioAssets, DataAssets};
+use crate::audio::LoopingSoundEvent;
+use crate::collision::{ContactEvent, Solid};
+use crate::entity::{DamageEvent, DeathEvent, GameEntity, Health, Hitbox, Motion, Team};
+use crate::entity::bullet::{spawn_bullet, Shooter};
+use crate::entity::enemy::{spawn_enemy, AttackCooldown, EnemyType};
+use crate::entity::player::Player;
+use crate::navigation::{FlowField, NavGrid};
+use crate::weapon::{WeaponDefinition, WeaponDefinitions};
+
+// size of the sprite before `BossDefinition::scale`
+const SPRITE_SIZE: Vec2 = Vec2::new(9., 17.);
+// distance from the boss at which summoned minions appear
+const SUMMON_DISTANCE: f32 = 30.;
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "c4e7a9d2-1b3f-4f6e-8a5d-7e2b9c0f4a16"]
+pub struct BossDefinitions {
+    pub bosses: Vec<BossDefinition>,
+}
+
+impl BossDefinitions {
+    pub fn get(&self, id: &str) -> Option<&BossDefinition> {
+        self.bosses.iter().find(|b| b.id == id)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BossDefinition {
+    pub id: String,
+    pub name: String,
+    pub health: u32,
+    // sprite and hitbox size compared to a regular enemy
+    pub scale: f32,
+    pub contact_damage: u32,
+    // half the width of the square the arena gets locked to, in tiles
+    pub arena_radius: u32,
+    // in order, each one starts once health drops to its threshold
+    pub phases: Vec<BossPhase>,
+}
+
+#[derive(Deserialize)]
+pub struct BossPhase {
+    // fraction of max health at which the phase starts, the first one should be 1
+    pub health: f32,
+    // max length of Motion.speed while walking at the player
+    pub speed: f32,
+    // seconds of walking between two attacks
+    pub pause: f32,
+    // used one after the other, starting over after the last
+    pub attacks: Vec<BossAttack>,
+}
+
+#[derive(Deserialize, Clone)]
+pub enum BossAttack {
+    // stands still for `windup` seconds, then runs where the player was for `duration` seconds
+    Charge { windup: f32, speed: f32, duration: f32 },
+    // `bullets` shots of an enemy weapon evenly around the boss, `waves` times `interval` seconds apart
+    Burst { weapon: String, bullets: u32, waves: u32, interval: f32 },
+    // minions appear in a ring around the boss
+    Summon { enemy: EnemyType, count: u32 },
+}
+
+#[derive(Default)]
+pub struct BossesLoader;
+
+impl AssetLoader for BossesLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let definitions: BossDefinitions = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(definitions));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["bosses.ron"]
+    }
+}
+
+// sent by the wave director, spawns the boss and locks the arena around the player
+pub struct SpawnBossEvent {
+    pub id: String,
+}
+
+#[derive(Component)]
+pub struct Boss {
+    // id in `BossDefinitions`
+    pub id: String,
+    // index in `BossDefinition::phases`
+    pub phase: usize,
+    // index of the next attack in the phase
+    next_attack: usize,
+    action: BossAction,
+    timer: Timer,
+}
+
+enum BossAction {
+    // walking at the player until the timer runs out
+    Chase,
+    // standing still before a charge
+    Windup { speed: f32, duration: f32 },
+    // velocity of the charge
+    Charging(Vec2),
+    // waves of a burst left after the current one
+    Bursting { weapon: String, bullets: u32, left: u32 },
+}
+
+// wall closing the arena while a boss is alive
+#[derive(Component)]
+pub struct ArenaBarrier;
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(SystemSet::on_update(AppState::Game(GameState::Playing))
+                .with_system(spawn_boss.after("waves").after("navigation"))
+                .with_system(boss_behaviour.after("navigation").before("movement"))
+                .with_system(boss_attack.after("collision").before("damage"))
+                .with_system(boss_death.after("damage"))
+            );
+        app.add_system_set(SystemSet::on_enter(AppState::Menu)
+            .with_system(despawn_bosses)
+        );
+        app.add_event::<SpawnBossEvent>();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_boss(
+    mut commands: Commands,
+    mut boss_events: EventReader<SpawnBossEvent>,
+    mut sound_events: EventWriter<LoopingSoundEvent>,
+    data: Res<DataAssets>,
+    boss_defs: Res<Assets<BossDefinitions>>,
+    animations: Res<AnimationAssets>,
+    audio: Res<AudioAssets>,
+    grid: Res<NavGrid>,
+    q_player: Query<&Transform, With<Player>>,
+) {
+    let player = match q_player.get_single() {
+        Ok(tf) => tf.translation.truncate(),
+        Err(_) => return,
+    };
+    for event in boss_events.iter() {
+        let boss = match boss_defs.get(&data.bosses).and_then(|b| b.get(&event.id)) {
+            Some(b) => b,
+            None => continue,
+        };
+        let position = lock_arena(&mut commands, &grid, player, boss.arena_radius as i32);
+        let size = SPRITE_SIZE * boss.scale;
+        let ent = commands.spawn_bundle(SpriteSheetBundle {
+            transform: Transform::from_translation(position.extend(0.5)),
+            sprite: TextureAtlasSprite {
+                custom_size: Some(size),
+                ..default()
+            },
+            ..default()
+        })
+            .insert(Animator::new(animations.mutant.clone(), IDLE))
+            .insert(Hitbox::Aabb(size))
+            .insert(Motion::new(0.05, 0.1))
+            .insert(Health::new(boss.health))
+            .insert(AttackCooldown(Timer::from_seconds(0.8, false)))
+            .insert(Team::Enemy)
+            .insert(GameEntity)
+            .insert(Boss {
+                id: boss.id.clone(),
+                phase: 0,
+                next_attack: 0,
+                action: BossAction::Chase,
+                timer: Timer::from_seconds(boss.phases.first().map_or(1., |p| p.pause), false),
+            })
+            .insert(Name::new(boss.name.clone()))
+            .id();
+        // heard for as long as the boss lives, louder the closer it gets
+        sound_events.send(LoopingSoundEvent {
+            sound: audio.roar.clone(),
+            emitter: ent,
+        });
+    }
+}
+
+// closes the walkable tiles on the edge of a square around the player, returns the open spot
+// inside it furthest from the player for the boss
+fn lock_arena(commands: &mut Commands, grid: &NavGrid, player: Vec2, radius: i32) -> Vec2 {
+    let center = match grid.tile(player) {
+        Some(t) => t.as_ivec2(),
+        None => return player,
+    };
+    let tile_size = grid.tile_size();
+    let open = |t: IVec2| t.x >= 0 && t.y >= 0 && grid.is_walkable(t.as_uvec2());
+    let mut spot = None;
+    for y in -radius..=radius {
+        for x in -radius..=radius {
+            let tile = center + IVec2::new(x, y);
+            if !open(tile) {
+                continue;
+            }
+            if x.abs() == radius || y.abs() == radius {
+                commands.spawn_bundle(SpriteBundle {
+                    transform: Transform::from_translation(grid.center(tile.as_uvec2()).extend(0.3)),
+                    sprite: Sprite {
+                        color: Color::rgba(0.6, 0.1, 0.1, 0.8),
+                        custom_size: Some(Vec2::splat(tile_size)),
+                        ..default()
+                    },
+                    ..default()
+                })
+                    .insert(Solid)
+                    .insert(Hitbox::Aabb(Vec2::splat(tile_size)))
+                    .insert(ArenaBarrier)
+                    .insert(Name::new("Arena barrier"));
+                continue;
+            }
+            // big enough for the boss to stand without touching a wall
+            let roomy = (-1..=1).all(|dy| (-1..=1).all(|dx| open(tile + IVec2::new(dx, dy))));
+            let distance = (tile - center).abs().max_element();
+            if roomy && spot.is_none_or(|(_, d)| distance > d) {
+                spot = Some((tile, distance));
+            }
+        }
+    }
+    spot.map_or(player, |(tile, _)| grid.center(tile.as_uvec2()))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn boss_behaviour(
+    mut commands: Commands,
+    data: Res<DataAssets>,
+    boss_defs: Res<Assets<BossDefinitions>>,
+    weapon_defs: Res<Assets<WeaponDefinitions>>,
+    animations: Res<AnimationAssets>,
+    grid: Res<NavGrid>,
+    flow: Res<FlowField>,
+    time: Res<Time>,
+    mut q_boss: Query<(Entity, &Transform, &Health, &mut Motion, &mut TextureAtlasSprite, &mut Boss)>,
+    q_player: Query<&Transform, With<Player>>,
+) {
+    let (bosses, weapons) = match (boss_defs.get(&data.bosses), weapon_defs.get(&data.weapons)) {
+        (Some(b), Some(w)) => (b, w),
+        _ => return,
+    };
+    let player = match q_player.get_single() {
+        Ok(tf) => tf.translation.truncate(),
+        Err(_) => return,
+    };
+    let delta = time.delta_seconds() * 100.0;
+    for (ent, tf, health, mut motion, mut sprite, mut boss) in q_boss.iter_mut() {
+        let definition = match bosses.get(&boss.id) {
+            Some(d) => d,
+            None => continue,
+        };
+        let position = tf.translation.truncate();
+        // phases only go forward, healing doesn't bring the old one back
+        let fraction = health.current as f32 / health.max as f32;
+        let phase_index = definition.phases.iter()
+            .rposition(|p| fraction <= p.health)
+            .unwrap_or(0);
+        if phase_index > boss.phase {
+            boss.phase = phase_index;
+            boss.next_attack = 0;
+            boss.action = BossAction::Chase;
+            boss.timer = Timer::from_seconds(definition.phases[phase_index].pause, false);
+        }
+        let phase = match definition.phases.get(boss.phase) {
+            Some(p) => p,
+            None => continue,
+        };
+        boss.timer.tick(time.delta());
+        let finished = boss.timer.finished();
+        sprite.color = Color::WHITE;
+        let mut steer = None;
+        match &mut boss.action {
+            BossAction::Chase => {
+                steer = if grid.line_of_sight(position, player) {
+                    (player - position).try_normalize()
+                } else {
+                    flow.direction(&grid, position)
+                };
+            }
+            BossAction::Windup { speed, duration } => {
+                // flashes before running so the player can step aside
+                sprite.color = Color::rgb(1., 0.4, 0.4);
+                if finished {
+                    let velocity = (player - position).normalize_or_zero() * *speed;
+                    let duration = *duration;
+                    boss.action = BossAction::Charging(velocity);
+                    boss.timer = Timer::from_seconds(duration, false);
+                }
+            }
+            BossAction::Charging(velocity) => {
+                motion.speed = *velocity;
+                if finished {
+                    boss.action = BossAction::Chase;
+                    boss.timer = Timer::from_seconds(phase.pause, false);
+                }
+                continue;
+            }
+            BossAction::Bursting { weapon, bullets, left } => {
+                if finished {
+                    if let Some(weapon) = weapons.get(weapon) {
+                        // every wave is turned by half a step so bullets don't follow the same lines
+                        let offset = *left as f32 * 0.5;
+                        burst(&mut commands, weapon, position, *bullets, offset, ent);
+                    }
+                    *left = left.saturating_sub(1);
+                    if *left == 0 {
+                        boss.action = BossAction::Chase;
+                        boss.timer = Timer::from_seconds(phase.pause, false);
+                    } else {
+                        boss.timer.reset();
+                    }
+                }
+            }
+        }
+        if matches!(boss.action, BossAction::Chase) && finished && !phase.attacks.is_empty() {
+            let attack = phase.attacks[boss.next_attack % phase.attacks.len()].clone();
+            boss.next_attack += 1;
+            match attack {
+                BossAttack::Charge { windup, speed, duration } => {
+                    boss.action = BossAction::Windup { speed, duration };
+                    boss.timer = Timer::from_seconds(windup, false);
+                    steer = None;
+                }
+                BossAttack::Burst { weapon, bullets, waves, interval } => {
+                    if let Some(definition) = weapons.get(&weapon) {
+                        burst(&mut commands, definition, position, bullets, 0., ent);
+                    }
+                    if waves > 1 {
+                        boss.action = BossAction::Bursting { weapon, bullets, left: waves - 1 };
+                        boss.timer = Timer::from_seconds(interval, false);
+                    } else {
+                        boss.timer = Timer::from_seconds(phase.pause, false);
+                    }
+                }
+                BossAttack::Summon { enemy, count } => {
+                    for i in 0..count {
+                        let angle = i as f32 / count as f32 * TAU;
+                        let offset = Vec2::new(angle.cos(), angle.sin()) * SUMMON_DISTANCE;
+                        spawn_enemy(&mut commands, &animations, enemy, position + offset);
+                    }
+                    boss.timer = Timer::from_seconds(phase.pause, false);
+                }
+            }
+        }
+        motion.steer(steer, phase.speed, delta);
+        if let Some(dir) = steer {
+            sprite.flip_x = dir.x < 0.;
+        }
+    }
+}
+
+// ring of `bullets` shots, `offset` is in steps between two bullets
+fn burst(
+    commands: &mut Commands,
+    weapon: &WeaponDefinition,
+    position: Vec2,
+    bullets: u32,
+    offset: f32,
+    shooter: Entity,
+) {
+    for i in 0..bullets {
+        let angle = (i as f32 + offset) / bullets as f32 * TAU;
+        let direction = Vec2::new(angle.cos(), angle.sin());
+        let bullet = spawn_bullet(commands, weapon, position.extend(0.5), direction, Team::Enemy);
+        commands.entity(bullet).insert(Shooter(shooter));
+    }
+}
+
+// contact damage, same as regular enemies
+fn boss_attack(
+    mut contact_events: EventReader<ContactEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
+    data: Res<DataAssets>,
+    boss_defs: Res<Assets<BossDefinitions>>,
+    mut q_boss: Query<(&Boss, &mut AttackCooldown)>,
+    q_player: Query<Entity, With<Player>>,
+    time: Res<Time>,
+) {
+    for (_, mut cooldown) in q_boss.iter_mut() {
+        cooldown.0.tick(time.delta());
+    }
+    for event in contact_events.iter() {
+        let (boss, player) = if q_player.get(event.b).is_ok() {
+            (event.a, event.b)
+        } else if q_player.get(event.a).is_ok() {
+            (event.b, event.a)
+        } else {
+            continue;
+        };
+        let (definition, mut cooldown) = match q_boss.get_mut(boss) {
+            Ok((b, c)) => match boss_defs.get(&data.bosses).and_then(|d| d.get(&b.id)) {
+                Some(d) => (d, c),
+                None => continue,
+            },
+            Err(_) => continue,
+        };
+        if !cooldown.0.finished() {
+            continue;
+        }
+        cooldown.0.reset();
+        damage_events.send(DamageEvent {
+            source: Some(boss),
+            target: player,
+            amount: definition.contact_damage,
+            bullet_type: None,
+        });
+    }
+}
+
+// the arena opens again once no boss is left
+#[allow(clippy::type_complexity)]
+fn boss_death(
+    mut commands: Commands,
+    mut death_events: EventReader<DeathEvent>,
+    q_boss: Query<(Entity, &Animator, &TextureAtlasSprite, &Handle<TextureAtlas>, &Transform), With<Boss>>,
+    q_barrier: Query<Entity, With<ArenaBarrier>>,
+) {
+    let mut dead = 0;
+    for event in death_events.iter() {
+        if let Ok((ent, animator, sprite, atlas, tf)) = q_boss.get(event.entity) {
+            spawn_corpse(&mut commands, animator, sprite, atlas, tf);
+            commands.entity(ent).despawn_recursive();
+            dead += 1;
+        }
+    }
+    if dead > 0 && dead >= q_boss.iter().count() {
+        for ent in q_barrier.iter() {
+            commands.entity(ent).despawn();
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn despawn_bosses(
+    mut commands: Commands,
+    q_ent: Query<Entity, Or<(With<Boss>, With<ArenaBarrier>)>>,
+) {
+    for ent in q_ent.iter() {
+        commands.entity(ent).despawn_recursive();
+    }
+}
@@ -0,0 +1,222 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{Inspectable, RegisterInspectable};
+use rand::Rng;
+use serde::Deserialize;
+use crate::{AppState, GameState};
+use crate::asset_loader::DataAssets;
+use crate::collision::{BulletHitEvent, PreviousPosition};
+use crate::entity::{DamageEvent, Health, Team};
+use crate::entity::explosion::ExplosionEvent;
+use crate::weapon::{WeaponDefinition, WeaponDefinitions};
+
+// normalized bullet direction, weapon id it was shot from, bullet origin
+#[derive(Component, Inspectable)]
+pub struct Bullet(Vec2, String, Vec3);
+
+// entity that fired the bullet, never hit by it
+#[derive(Component)]
+pub struct Shooter(pub Entity);
+
+#[derive(Component, Inspectable, Clone, Deserialize)]
+pub enum BulletType {
+    Basic,
+    Rocket,
+    Bone,
+    Spore,
+}
+
+impl BulletType {
+    // how far enemies hear the shot
+    pub fn noise_radius(&self) -> f32 {
+        match self {
+            BulletType::Basic => 160.,
+            BulletType::Rocket => 700.,
+            BulletType::Bone | BulletType::Spore => 0.,
+        }
+    }
+}
+
+pub struct BulletPlugin;
+
+impl Plugin for BulletPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(SystemSet::on_update(AppState::Game(GameState::Playing))
+                .with_system(move_bullet.label("movement"))
+                .with_system(bullet_hit.label("hit").after("collision"))
+                .with_system(expire_bullets.after("hit"))
+            );
+        app.add_system_set(SystemSet::on_enter(AppState::Menu)
+            .with_system(despawn_bullets)
+        );
+        app.register_inspectable::<Bullet>();
+        app.register_inspectable::<BulletType>();
+    }
+}
+
+// one shot of `weapon`, `pellets` bullets spread around `aim`, `bloom` degrees wider than the weapon's spread
+pub fn fire_weapon(
+    commands: &mut Commands,
+    weapon: &WeaponDefinition,
+    origin: Vec3,
+    aim: Vec2,
+    bloom: f32,
+    team: Team,
+    shooter: Option<Entity>,
+) {
+    let max_spread = weapon.spread + bloom;
+    for _ in 0..weapon.pellets {
+        let spread = if max_spread > 0. {
+            rand::thread_rng().gen_range(-max_spread..=max_spread).to_radians()
+        } else {
+            0.
+        };
+        let direction = (Quat::from_rotation_z(spread) * aim.extend(0.)).truncate();
+        let bullet = spawn_bullet(commands, weapon, origin, direction, team);
+        if let Some(shooter) = shooter {
+            commands.entity(bullet).insert(Shooter(shooter));
+        }
+    }
+}
+
+pub fn spawn_bullet(
+    commands: &mut Commands,
+    weapon: &WeaponDefinition,
+    origin: Vec3,
+    direction: Vec2,
+    team: Team,
+) -> Entity {
+    let direction = direction.normalize_or_zero();
+    commands.spawn_bundle(SpriteBundle {
+        transform: Transform {
+            translation: origin - Vec3::new(0., 0., 1.),
+            rotation: Quat::from_rotation_z(direction.y.atan2(direction.x)),
+            ..default()
+        },
+        sprite: Sprite {
+            flip_y: direction.x < 0.,
+            ..default()
+        },
+        texture: weapon.bullet_image.clone(),
+        ..default()
+    })
+        .insert(Bullet(direction, weapon.id.clone(), origin))
+        .insert(team)
+        .insert(PreviousPosition(origin.truncate()))
+        .id()
+}
+
+fn despawn_bullets(
+    mut commands: Commands,
+    q_ent: Query<Entity, With<Bullet>>,
+) {
+    for ent in q_ent.iter() {
+        commands.entity(ent).despawn_recursive();
+    }
+}
+
+fn move_bullet(
+    mut commands: Commands,
+    data: Res<DataAssets>,
+    weapon_defs: Res<Assets<WeaponDefinitions>>,
+    mut q_bullet: Query<(&mut Transform, &Bullet, Entity)>,
+    time: Res<Time>,
+) {
+    // bullet speeds are in units per frame at 60 fps
+    let delta = time.delta_seconds() * 60.0;
+    let weapons = match weapon_defs.get(&data.weapons) {
+        Some(w) => w,
+        None => return,
+    };
+    for (mut tf, bt, ent) in q_bullet.iter_mut() {
+        let weapon = match weapons.get(&bt.1) {
+            Some(w) => w,
+            None => {
+                commands.entity(ent).despawn();
+                continue;
+            }
+        };
+        tf.translation += (bt.0 * weapon.bullet_speed * delta).extend(0.);
+    }
+}
+
+// after `bullet_hit`, so a bullet that hits something on its last step doesn't also explode at max range
+fn expire_bullets(
+    mut commands: Commands,
+    mut hit_events: EventReader<BulletHitEvent>,
+    mut explosion_events: EventWriter<ExplosionEvent>,
+    data: Res<DataAssets>,
+    weapon_defs: Res<Assets<WeaponDefinitions>>,
+    q_bullet: Query<(Entity, &Transform, &Bullet, &Team, Option<&Shooter>)>,
+) {
+    let weapons = match weapon_defs.get(&data.weapons) {
+        Some(w) => w,
+        None => return,
+    };
+    let hit: Vec<Entity> = hit_events.iter().map(|e| e.bullet).collect();
+    for (ent, tf, bt, team, shooter) in q_bullet.iter() {
+        if hit.contains(&ent) {
+            continue;
+        }
+        let weapon = match weapons.get(&bt.1) {
+            Some(w) => w,
+            None => continue,
+        };
+        if bt.2.distance(tf.translation) > weapon.range {
+            if weapon.explosion.is_some() {
+                explosion_events.send(ExplosionEvent {
+                    position: tf.translation.truncate(),
+                    weapon: bt.1.clone(),
+                    team: *team,
+                    source: shooter.map(|s| s.0),
+                });
+            }
+            commands.entity(ent).despawn();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn bullet_hit(
+    mut commands: Commands,
+    mut hit_events: EventReader<BulletHitEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut explosion_events: EventWriter<ExplosionEvent>,
+    data: Res<DataAssets>,
+    weapon_defs: Res<Assets<WeaponDefinitions>>,
+    q_bullet: Query<(&Bullet, &Team, Option<&Shooter>)>,
+    q_health: Query<(), With<Health>>,
+) {
+    let weapons = match weapon_defs.get(&data.weapons) {
+        Some(w) => w,
+        None => return,
+    };
+    for event in hit_events.iter() {
+        let (bullet, team, shooter) = match q_bullet.get(event.bullet) {
+            Ok(b) => b,
+            Err(_) => continue,
+        };
+        let weapon = match weapons.get(&bullet.1) {
+            Some(w) => w,
+            None => continue,
+        };
+        let source = shooter.map(|s| s.0);
+        if q_health.get(event.target).is_ok() {
+            damage_events.send(DamageEvent {
+                source,
+                target: event.target,
+                amount: weapon.damage,
+                bullet_type: Some(weapon.bullet.clone()),
+            });
+        }
+        if weapon.explosion.is_some() {
+            explosion_events.send(ExplosionEvent {
+                position: event.point,
+                weapon: bullet.1.clone(),
+                team: *team,
+                source,
+            });
+        }
+        commands.entity(event.bullet).despawn();
+    }
+}
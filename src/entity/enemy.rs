@@ -0,0 +1,279 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{Inspectable, RegisterInspectable};
+use serde::Deserialize;
+use crate::{AppState, GameState};
+use crate::animation::{spawn_corpse, AnimationSet, Animator, IDLE};
+use crate::asset_loader::{AnimationAssets, DataAssets};
+use crate::audio::{play_sound, SoundEvent};
+use crate::collision::ContactEvent;
+use crate::entity::{DamageEvent, DeathEvent, GameEntity, Health, Hitbox, Motion, ShotEvent, Team};
+use crate::entity::ai::{Ai, AiState};
+use crate::entity::bullet::fire_weapon;
+use crate::entity::player::Player;
+use crate::navigation::{NavGrid, PathFollower};
+use crate::weapon::WeaponDefinitions;
+
+#[derive(Component, Inspectable)]
+pub struct Enemy(pub EnemyType);
+
+// time between contact hits
+#[derive(Component)]
+pub struct AttackCooldown(pub Timer);
+
+// time until the next shot of an enemy with an `EnemyStats::weapon`
+#[derive(Component)]
+pub struct RangedAttack(pub Timer);
+
+#[derive(Component, Inspectable, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EnemyType {
+    Zombie,
+    Skeleton,
+    Mutant,
+}
+
+pub struct EnemyStats {
+    pub health: u32,
+    // max length of Motion.speed
+    pub speed: f32,
+    // damage dealt on contact
+    pub damage: u32,
+    // distance at which the player gets noticed, when no wall is in the way
+    pub sight: f32,
+    // fraction of max health below which the enemy runs away, 0 never does
+    pub flee_health: f32,
+    // id in `WeaponDefinitions::enemy_weapons`, fired at the player while in sight
+    pub weapon: Option<&'static str>,
+    // ranged enemies stop closing in at this distance while they can see the player
+    pub keep_distance: f32,
+}
+
+impl EnemyType {
+    pub fn stats(&self) -> EnemyStats {
+        match self {
+            EnemyType::Zombie => EnemyStats {
+                health: 30,
+                speed: 1.2,
+                damage: 10,
+                sight: 90.,
+                flee_health: 0.,
+                weapon: None,
+                keep_distance: 0.,
+            },
+            EnemyType::Skeleton => EnemyStats {
+                health: 20,
+                speed: 1.8,
+                damage: 6,
+                sight: 130.,
+                flee_health: 0.3,
+                weapon: Some("bone"),
+                keep_distance: 90.,
+            },
+            EnemyType::Mutant => EnemyStats {
+                health: 120,
+                speed: 0.8,
+                damage: 25,
+                sight: 70.,
+                flee_health: 0.,
+                weapon: None,
+                keep_distance: 0.,
+            },
+        }
+    }
+
+    fn animations(&self, animations: &AnimationAssets) -> Handle<AnimationSet> {
+        match self {
+            EnemyType::Zombie => animations.zombie.clone(),
+            EnemyType::Skeleton => animations.skeleton.clone(),
+            EnemyType::Mutant => animations.mutant.clone(),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            EnemyType::Zombie => "Zombie",
+            EnemyType::Skeleton => "Skeleton",
+            EnemyType::Mutant => "Mutant",
+        }
+    }
+}
+
+pub struct EnemyPlugin;
+
+impl Plugin for EnemyPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(SystemSet::on_update(AppState::Game(GameState::Playing))
+                .with_system(enemy_attack.after("collision").before("damage"))
+                .with_system(enemy_shoot.after("ai"))
+                .with_system(enemy_death.after("damage"))
+            );
+        app.add_system_set(SystemSet::on_enter(AppState::Menu)
+            .with_system(despawn_enemies)
+        );
+        app.register_inspectable::<Enemy>();
+        app.register_inspectable::<EnemyType>();
+    }
+}
+
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    animations: &AnimationAssets,
+    enemy_type: EnemyType,
+    position: Vec2,
+) -> Entity {
+    let mut enemy = commands.spawn_bundle(EnemyBundle::new(enemy_type, animations, position));
+    enemy
+        .insert(GameEntity)
+        .insert(Name::new(enemy_type.name()));
+    // mutants are few and slow, they can afford their own path instead of the shared flow field
+    if enemy_type == EnemyType::Mutant {
+        enemy.insert(PathFollower::default());
+    }
+    if enemy_type.stats().weapon.is_some() {
+        // first shot comes a moment after noticing the player
+        enemy.insert(RangedAttack(Timer::from_seconds(1., false)));
+    }
+    enemy.id()
+}
+
+fn despawn_enemies(
+    mut commands: Commands,
+    q_ent: Query<Entity, With<Enemy>>,
+) {
+    for ent in q_ent.iter() {
+        commands.entity(ent).despawn_recursive();
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn enemy_death(
+    mut commands: Commands,
+    mut death_events: EventReader<DeathEvent>,
+    q_enemy: Query<(Entity, &Animator, &TextureAtlasSprite, &Handle<TextureAtlas>, &Transform), With<Enemy>>,
+) {
+    for event in death_events.iter() {
+        if let Ok((ent, animator, sprite, atlas, tf)) = q_enemy.get(event.entity) {
+            spawn_corpse(&mut commands, animator, sprite, atlas, tf);
+            commands.entity(ent).despawn_recursive();
+        }
+    }
+}
+
+fn enemy_attack(
+    mut contact_events: EventReader<ContactEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut q_enemy: Query<(&Enemy, &mut AttackCooldown)>,
+    q_player: Query<Entity, With<Player>>,
+    time: Res<Time>,
+) {
+    for (_, mut cooldown) in q_enemy.iter_mut() {
+        cooldown.0.tick(time.delta());
+    }
+    for event in contact_events.iter() {
+        let (enemy, player) = if q_player.get(event.b).is_ok() {
+            (event.a, event.b)
+        } else if q_player.get(event.a).is_ok() {
+            (event.b, event.a)
+        } else {
+            continue;
+        };
+        let (enemy_type, mut cooldown) = match q_enemy.get_mut(enemy) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        if !cooldown.0.finished() {
+            continue;
+        }
+        cooldown.0.reset();
+        damage_events.send(DamageEvent {
+            source: Some(enemy),
+            target: player,
+            amount: enemy_type.0.stats().damage,
+            bullet_type: None,
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn enemy_shoot(
+    mut commands: Commands,
+    mut sound_events: EventWriter<SoundEvent>,
+    mut shot_events: EventWriter<ShotEvent>,
+    data: Res<DataAssets>,
+    weapon_defs: Res<Assets<WeaponDefinitions>>,
+    grid: Res<NavGrid>,
+    time: Res<Time>,
+    mut q_enemy: Query<(Entity, &Transform, &Enemy, &Ai, &mut RangedAttack)>,
+    q_player: Query<&Transform, With<Player>>,
+) {
+    let weapons = match weapon_defs.get(&data.weapons) {
+        Some(w) => w,
+        None => return,
+    };
+    let player = match q_player.get_single() {
+        Ok(tf) => tf.translation.truncate(),
+        Err(_) => return,
+    };
+    for (ent, tf, enemy, ai, mut cooldown) in q_enemy.iter_mut() {
+        cooldown.0.tick(time.delta());
+        if !cooldown.0.finished() || !matches!(ai.state, AiState::Chase | AiState::Attack) {
+            continue;
+        }
+        let weapon = match enemy.0.stats().weapon.and_then(|id| weapons.get(id)) {
+            Some(w) => w,
+            None => continue,
+        };
+        let origin = tf.translation + Vec3::new(weapon.offset.0, weapon.offset.1, 0.);
+        let position = origin.truncate();
+        if position.distance(player) > weapon.range || !grid.line_of_sight(position, player) {
+            continue;
+        }
+        cooldown.0 = Timer::from_seconds(1. / weapon.fire_rate, false);
+        fire_weapon(&mut commands, weapon, origin, player - position, 0., Team::Enemy, Some(ent));
+        play_sound(&mut sound_events, &weapon.fire_audio, position);
+        shot_events.send(ShotEvent {
+            shooter: ent,
+            gun: None,
+            weapon: weapon.id.clone(),
+        });
+    }
+}
+
+#[derive(Bundle)]
+pub struct EnemyBundle {
+    pub sprite: TextureAtlasSprite,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+    pub texture_atlas: Handle<TextureAtlas>,
+    pub hitbox: Hitbox,
+    pub visibility: Visibility,
+    pub computed_visibility: ComputedVisibility,
+    pub motion: Motion,
+    pub health: Health,
+    pub cooldown: AttackCooldown,
+    pub enemy: Enemy,
+    pub ai: Ai,
+    pub team: Team,
+    pub animator: Animator,
+}
+
+impl EnemyBundle {
+    pub fn new(enemy_type: EnemyType, animations: &AnimationAssets, position: Vec2) -> Self {
+        Self {
+            sprite: Default::default(),
+            transform: Transform::from_translation(position.extend(0.5)),
+            global_transform: Default::default(),
+            texture_atlas: Default::default(),
+            hitbox: Hitbox::Aabb(Vec2::new(9., 17.)),
+            visibility: Default::default(),
+            computed_visibility: Default::default(),
+            motion: Motion::new(0.05, 0.1),
+            health: Health::new(enemy_type.stats().health),
+            cooldown: AttackCooldown(Timer::from_seconds(0.8, false)),
+            enemy: Enemy(enemy_type),
+            ai: Ai::default(),
+            team: Team::Enemy,
+            animator: Animator::new(enemy_type.animations(animations), IDLE),
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use bevy::prelude::*;
+use crate::{AppState, GameState};
+use crate::asset_loader::{AudioAssets, DataAssets, TextureAssets};
+use crate::audio::SoundEvent;
+use crate::collision::grid::SpatialGrid;
+use crate::entity::{DamageEvent, Health, Motion, Team};
+use crate::weapon::WeaponDefinitions;
+
+// sent when a bullet with an `explosion` in its weapon definition hits something or runs out of range
+pub struct ExplosionEvent {
+    pub position: Vec2,
+    pub weapon: String,
+    // side and entity that fired the bullet
+    pub team: Team,
+    pub source: Option<Entity>,
+}
+
+// lifetime, radius
+#[derive(Component)]
+struct ExplosionEffect(Timer, f32);
+
+pub struct ExplosionPlugin;
+
+impl Plugin for ExplosionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(SystemSet::on_update(AppState::Game(GameState::Playing))
+                .with_system(explode.label("explode").after("hit").before("damage"))
+                .with_system(explosion_effect)
+            );
+        app.add_system_set(SystemSet::on_enter(AppState::Menu)
+            .with_system(despawn_explosions)
+        );
+        app.add_event::<ExplosionEvent>();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn explode(
+    mut commands: Commands,
+    mut explosion_events: EventReader<ExplosionEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut sound_events: EventWriter<SoundEvent>,
+    grid: Res<SpatialGrid>,
+    texture: Res<TextureAssets>,
+    audio: Res<AudioAssets>,
+    data: Res<DataAssets>,
+    weapon_defs: Res<Assets<WeaponDefinitions>>,
+    q_health: Query<Option<&Team>, With<Health>>,
+    mut q_motion: Query<&mut Motion>,
+) {
+    let weapons = match weapon_defs.get(&data.weapons) {
+        Some(w) => w,
+        None => return,
+    };
+    for event in explosion_events.iter() {
+        let weapon = match weapons.get(&event.weapon) {
+            Some(w) => w,
+            None => continue,
+        };
+        let explosion = match &weapon.explosion {
+            Some(e) => e,
+            None => continue,
+        };
+        for (ent, shape) in grid.neighbours(event.position, explosion.radius) {
+            // measured to the closest point so big hitboxes get hit by their edge
+            let closest = event.position.clamp(
+                shape.center() - shape.half_extents(),
+                shape.center() + shape.half_extents(),
+            );
+            let falloff = (1. - closest.distance(event.position) / explosion.radius).clamp(0., 1.);
+            let hurts = match q_health.get(ent) {
+                Ok(_) if event.source == Some(ent) => explosion.self_damage,
+                Ok(Some(team)) if *team == event.team => weapons.friendly_fire.allows(event.team),
+                Ok(_) => true,
+                Err(_) => false,
+            };
+            if hurts {
+                let amount = (explosion.damage as f32 * falloff).round() as u32;
+                if amount > 0 {
+                    damage_events.send(DamageEvent {
+                        source: event.source,
+                        target: ent,
+                        amount,
+                        bullet_type: Some(weapon.bullet.clone()),
+                    });
+                }
+            }
+            if let Ok(mut motion) = q_motion.get_mut(ent) {
+                let dir = (shape.center() - event.position).normalize_or_zero();
+                motion.impulse += dir * explosion.knockback * falloff;
+            }
+        }
+        commands.spawn_bundle(SpriteBundle {
+            transform: Transform::from_translation(event.position.extend(2.)),
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(explosion.radius * 2.)),
+                ..default()
+            },
+            texture: texture.explosion.clone(),
+            ..default()
+        })
+            .insert(ExplosionEffect(Timer::from_seconds(0.3, false), explosion.radius))
+            .insert(Name::new("Explosion"));
+        sound_events.send(SoundEvent {
+            sound: audio.explosion.clone(),
+            position: Some(event.position),
+        });
+    }
+}
+
+fn explosion_effect(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q_effect: Query<(Entity, &mut ExplosionEffect, &mut Sprite)>,
+) {
+    for (ent, mut effect, mut sprite) in q_effect.iter_mut() {
+        effect.0.tick(time.delta());
+        if effect.0.finished() {
+            commands.entity(ent).despawn();
+            continue;
+        }
+        let progress = effect.0.percent();
+        sprite.custom_size = Some(Vec2::splat(effect.1 * 2. * (0.5 + progress / 2.)));
+        sprite.color.set_a(1. - progress);
+    }
+}
+
+fn despawn_explosions(
+    mut commands: Commands,
+    q_ent: Query<Entity, With<ExplosionEffect>>,
+) {
+    for ent in q_ent.iter() {
+        commands.entity(ent).despawn_recursive();
+    }
+}
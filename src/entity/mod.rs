@@ -1,13 +1,25 @@
 pub(crate) mod player;
+pub(crate) mod bullet;
+pub(crate) mod enemy;
+pub(crate) mod explosion;
+pub(crate) mod pickup;
+pub(crate) mod ai;
+pub(crate) mod boss;
 
 use bevy::prelude::*;
 use bevy_inspector_egui::{Inspectable, RegisterInspectable};
 use crate::{AppState, GameState};
+use crate::entity::ai::AiPlugin;
+use crate::entity::boss::BossPlugin;
+use crate::entity::bullet::{BulletPlugin, BulletType};
+use crate::entity::enemy::EnemyPlugin;
+use crate::entity::explosion::ExplosionPlugin;
+use crate::entity::pickup::PickupPlugin;
 use crate::entity::player::PlayerPlugin;
 
 
 #[derive(Component)]
-struct GameEntity;
+pub(crate) struct GameEntity;
 
 #[derive(Component, Inspectable)]
 pub struct Motion {
@@ -16,40 +28,157 @@ pub struct Motion {
     #[inspectable(min = 0.001, max = 2.0)]
     pub dcc: f32,
     pub speed: Vec2,
+    // knockback, added on top of speed and decays on its own
+    pub impulse: Vec2,
 }
 
-#[derive(Component)]
-pub struct Hitbox(Vec2);
+impl Motion {
+    // speeds up towards `direction` without going over `max_speed`, slows down to a stop without one
+    pub fn steer(&mut self, direction: Option<Vec2>, max_speed: f32, delta: f32) {
+        match direction {
+            Some(dir) => {
+                self.speed += dir * self.acc * delta;
+                self.speed = self.speed.clamp_length_max(max_speed);
+            }
+            None => {
+                self.speed -= self.speed * self.dcc * delta.clamp(0.0, 0.9);
+            }
+        }
+    }
+}
+
+// size of the collider, see `collision::Shape`
+#[derive(Component, Clone, Copy)]
+pub enum Hitbox {
+    Aabb(Vec2),
+    Circle(f32),
+}
 
 #[derive(Component, Inspectable, Default)]
 pub struct Controllable {
     pub is_controllable: bool,
 }
 
+// side an entity or bullet is on, bullets pass through their own side unless friendly fire is on for it
+#[derive(Component, Inspectable, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Team {
+    Player,
+    Enemy,
+}
+
+#[derive(Component, Inspectable)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+}
+
+impl Health {
+    pub fn new(max: u32) -> Self {
+        Self {
+            current: max,
+            max,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current == 0
+    }
+}
+
+// sent by anything that wants to hurt an entity, applied in `apply_damage`
+pub struct DamageEvent {
+    pub source: Option<Entity>,
+    pub target: Entity,
+    pub amount: u32,
+    // nothing reacts to the kind of bullet yet
+    #[allow(dead_code)]
+    pub bullet_type: Option<BulletType>,
+}
+
+// something loud happened, enemies within `radius` come to have a look
+pub struct NoiseEvent {
+    pub position: Vec2,
+    pub radius: f32,
+}
+
+// a weapon went off, `gun` is the entity of the gun when the shooter holds one
+pub struct ShotEvent {
+    pub shooter: Entity,
+    pub gun: Option<Entity>,
+    // id in `WeaponDefinitions`
+    pub weapon: String,
+}
+
+// sent once when an entity's health reaches zero, the entity is not despawned here
+pub struct DeathEvent {
+    pub entity: Entity,
+    // nothing gives credit for kills yet
+    #[allow(dead_code)]
+    pub killer: Option<Entity>,
+}
+
 pub struct EntityPlugin;
 
 impl Plugin for EntityPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(PlayerPlugin);
+        app.add_plugin(BulletPlugin);
+        app.add_plugin(EnemyPlugin);
+        app.add_plugin(ExplosionPlugin);
+        app.add_plugin(PickupPlugin);
+        app.add_plugin(AiPlugin);
+        app.add_plugin(BossPlugin);
         app.add_system_set(SystemSet::on_update(AppState::Game(GameState::Playing))
             .with_system(entity_motion.label("movement"))
+            .with_system(apply_damage.label("damage").after("shoot").after("hit"))
         );
+        app.add_event::<DamageEvent>();
+        app.add_event::<DeathEvent>();
+        app.add_event::<NoiseEvent>();
+        app.add_event::<ShotEvent>();
         app.register_inspectable::<Motion>();
         app.register_inspectable::<Controllable>();
+        app.register_inspectable::<Health>();
+        app.register_inspectable::<Team>();
     }
 }
 
 fn entity_motion(
-    mut q_motion: Query<(&mut Transform, &Motion), With<GameEntity>>,
+    mut q_motion: Query<(&mut Transform, &mut Motion), With<GameEntity>>,
     time: Res<Time>,
 ) {
     let delta = time.delta_seconds() * 100.0;
-    for (mut movement, motion) in q_motion.iter_mut() {
-        movement.translation.x += motion.speed.x * delta;
-        movement.translation.y += motion.speed.y * delta;
+    for (mut movement, mut motion) in q_motion.iter_mut() {
+        movement.translation.x += (motion.speed.x + motion.impulse.x) * delta;
+        movement.translation.y += (motion.speed.y + motion.impulse.y) * delta;
+        let impulse = motion.impulse;
+        motion.impulse -= impulse * (0.1 * delta).clamp(0.0, 1.0);
     }
 }
 
+fn apply_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    mut death_events: EventWriter<DeathEvent>,
+    mut q_health: Query<&mut Health>,
+) {
+    for event in damage_events.iter() {
+        let mut health = match q_health.get_mut(event.target) {
+            Ok(h) => h,
+            Err(_) => continue,
+        };
+        // already dead, don't send the death event twice
+        if health.is_dead() {
+            continue;
+        }
+        health.current -= event.amount.min(health.current);
+        if health.is_dead() {
+            death_events.send(DeathEvent {
+                entity: event.target,
+                killer: event.source,
+            });
+        }
+    }
+}
 
 impl Motion {
     fn new(acc: f32, dcc: f32) -> Self {
@@ -57,6 +186,7 @@ impl Motion {
             acc,
             dcc,
             speed: Vec2::new(0.0, 0.0),
+            impulse: Vec2::ZERO,
         }
     }
 }
@@ -67,13 +197,14 @@ impl Default for Motion {
             acc: 1.0,
             dcc: 1.0,
             speed: Vec2::new(0.0, 0.0),
+            impulse: Vec2::ZERO,
         }
     }
 }
 
 impl Default for Hitbox {
     fn default() -> Self {
-        Self(Vec2::ZERO)
+        Self::Aabb(Vec2::ZERO)
     }
 }
 
@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use crate::{AppState, GameState};
+use crate::asset_loader::TextureAssets;
+use crate::entity::{Health, Hitbox};
+use crate::entity::player::{Ammo, Player, UsingGun};
+
+// radius of a pickup's hitbox, it gets collected once the player's touches it
+const PICKUP_RADIUS: f32 = 5.;
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Pickup {
+    pub kind: PickupKind,
+    pub amount: u32,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PickupKind {
+    // heals the player, up to their max health
+    Health,
+    // added to the reserve of the gun in hand
+    Ammo,
+}
+
+impl PickupKind {
+    fn texture(&self, texture: &TextureAssets) -> Handle<Image> {
+        match self {
+            PickupKind::Health => texture.health.clone(),
+            PickupKind::Ammo => texture.ammo.clone(),
+        }
+    }
+}
+
+pub struct PickupPlugin;
+
+impl Plugin for PickupPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(SystemSet::on_update(AppState::Game(GameState::Playing))
+                .with_system(collect_pickups.after("collision"))
+            );
+        app.add_system_set(SystemSet::on_enter(AppState::Menu)
+            .with_system(despawn_pickups)
+        );
+    }
+}
+
+pub fn spawn_pickup(
+    commands: &mut Commands,
+    texture: &TextureAssets,
+    pickup: Pickup,
+    position: Vec2,
+) -> Entity {
+    commands.spawn_bundle(SpriteBundle {
+        transform: Transform::from_translation(position.extend(0.4)),
+        texture: pickup.kind.texture(texture),
+        ..default()
+    })
+        .insert(pickup)
+        .insert(Hitbox::Circle(PICKUP_RADIUS))
+        .insert(Name::new(format!("{:?} pickup", pickup.kind)))
+        .id()
+}
+
+fn despawn_pickups(
+    mut commands: Commands,
+    q_ent: Query<Entity, With<Pickup>>,
+) {
+    for ent in q_ent.iter() {
+        commands.entity(ent).despawn_recursive();
+    }
+}
+
+fn collect_pickups(
+    mut commands: Commands,
+    q_pickup: Query<(Entity, &Transform, &Hitbox, &Pickup)>,
+    mut q_player: Query<(&Transform, &Hitbox, &mut Health), With<Player>>,
+    mut q_gun: Query<&mut Ammo, With<UsingGun>>,
+) {
+    let (player_tf, player_hitbox, mut health) = match q_player.get_single_mut() {
+        Ok(p) => p,
+        Err(_) => return,
+    };
+    let player = player_hitbox.shape(player_tf.translation.truncate());
+    for (ent, tf, hitbox, pickup) in q_pickup.iter() {
+        if hitbox.shape(tf.translation.truncate()).overlap(&player).is_none() {
+            continue;
+        }
+        match pickup.kind {
+            PickupKind::Health => {
+                // full health leaves the pickup for later
+                if health.current >= health.max {
+                    continue;
+                }
+                health.current = (health.current + pickup.amount).min(health.max);
+            }
+            PickupKind::Ammo => {
+                let mut ammo = match q_gun.get_single_mut() {
+                    Ok(a) => a,
+                    Err(_) => continue,
+                };
+                ammo.0 += pickup.amount;
+            }
+        }
+        commands.entity(ent).despawn_recursive();
+    }
+}
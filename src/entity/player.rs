@@ -1,11 +1,20 @@
 use std::cmp::min;
+use std::f32::consts::{PI, TAU};
 use bevy::prelude::*;
-use bevy::render::texture::DEFAULT_IMAGE_HANDLE;
 use bevy_inspector_egui::{RegisterInspectable, Inspectable};
 use crate::{AppState, GameState};
-use crate::asset_loader::TextureAssets;
-use crate::entity::{Controllable, GameEntity, Hitbox, Motion};
+use crate::animation::{Animator, DIE, IDLE};
+use crate::asset_loader::{AnimationAssets, DataAssets};
+use crate::audio::{play_sound, SoundEvent};
+use crate::camera::cursor_to_world;
+use crate::entity::{Controllable, DeathEvent, GameEntity, Health, Hitbox, Motion, NoiseEvent, ShotEvent, Team};
+use crate::entity::bullet::fire_weapon;
+use crate::input::{Action, GamepadSticks, InputDevice};
+use crate::level::{CurrentLevel, Level};
+use crate::weapon::{FireMode, ReloadAnimation, WeaponDefinition, WeaponDefinitions};
 
+// fraction of the gun kick recovered per second
+const KICK_RECOVERY: f32 = 12.;
 
 #[derive(Component)]
 pub struct Player;
@@ -13,15 +22,27 @@ pub struct Player;
 #[derive(Component)]
 pub struct UsingGun;
 
-// inside magazine, magazine size, bullet type it shoots
+// inside magazine, magazine size, weapon id in `WeaponDefinitions`
 #[derive(Component, Inspectable)]
-pub struct Gun(pub u32, pub u32, BulletType);
+pub struct Gun(pub u32, pub u32, pub String);
 
 impl Gun {
+    // tops the magazine up, rounds still in it stay there
     fn reload(&mut self, ammo: &mut Ammo) {
-        let amount = min(ammo.0, self.1);
-        ammo.remove(self.1 - self.0);
-        self.0 = amount;
+        let amount = min(self.1 - self.0, ammo.0);
+        ammo.remove(amount);
+        self.0 += amount;
+    }
+
+    fn load_shell(&mut self, ammo: &mut Ammo) {
+        if ammo.0 > 0 && self.0 < self.1 {
+            ammo.remove(1);
+            self.0 += 1;
+        }
+    }
+
+    fn can_reload(&self, ammo: &Ammo) -> bool {
+        ammo.0 > 0 && self.0 < self.1
     }
 }
 
@@ -34,25 +55,39 @@ impl Ammo {
     }
 }
 
-// bullet direction, bullet type, bullet origin
-#[derive(Component, Inspectable)]
-pub struct Bullet(f32, BulletType, Vec3);
+// trigger state of a gun, how it's used depends on the weapon's `FireMode`
+#[derive(Component, Inspectable, Default)]
+pub struct FireControl {
+    // seconds until the next shot is allowed
+    pub cooldown: f32,
+    // shots left in the current burst
+    pub burst_left: u32,
+    // seconds the trigger has been held
+    pub charge: f32,
+    // degrees of spread added by sustained fire
+    pub bloom: f32,
+    // how far the gun is pushed back from its offset
+    pub kick: f32,
+}
 
-#[derive(Component, Inspectable, Clone)]
-pub enum BulletType {
-    Basic,
-    Rocket,
+// present on a gun while it reloads, removed when done or cancelled
+#[derive(Component)]
+pub struct Reloading {
+    pub timer: Timer,
+    // per shell reloads stop after the current shell
+    pub interrupted: bool,
 }
 
-impl Bullet {
-    fn speed(&self) -> f32 {
-        match self.1 {
-            BulletType::Basic => 50.,
-            BulletType::Rocket => 10.,
-        }
+impl Reloading {
+    pub fn progress(&self) -> f32 {
+        self.timer.percent()
     }
 }
 
+// normalized direction the player is aiming in, in world space
+#[derive(Component, Inspectable)]
+pub struct Aim(pub Vec2);
+
 #[derive(Component, Inspectable)]
 pub struct Belt(i8);
 
@@ -62,65 +97,73 @@ impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_system_set(SystemSet::on_enter(AppState::Game(GameState::Playing))
-                .with_system(spawn_player_with_guns)
+                .with_system(spawn_player_with_guns.after("level"))
             )
             .add_system_set(SystemSet::on_update(AppState::Game(GameState::Playing))
                 .with_system(control_player)
-                .with_system(move_gun.after("change").label("gun"))
+                .with_system(recover_recoil.before("gun"))
+                .with_system(move_gun.after("change").after("camera").label("gun"))
                 .with_system(shoot.after("gun").after("change").label("shoot"))
-                .with_system(move_bullet)
                 .with_system(change_gun.label("change"))
-                .with_system(manual_reload.after("change"))
+                .with_system(manual_reload.after("change").label("reload"))
+                .with_system(reload_gun.after("reload").after("gun"))
+                .with_system(player_death.after("damage"))
             );
         app.add_system_set(SystemSet::on_enter(AppState::Menu)
             .with_system(despawn_player)
         );
         app.register_inspectable::<Belt>();
+        app.register_inspectable::<Aim>();
         app.register_inspectable::<Gun>();
         app.register_inspectable::<Ammo>();
-        app.register_inspectable::<Bullet>();
-        app.register_inspectable::<BulletType>();
+        app.register_inspectable::<FireControl>();
     }
 }
 
 fn spawn_player_with_guns(
     mut commands: Commands,
-    texture: Res<TextureAssets>,
+    animations: Res<AnimationAssets>,
+    data: Res<DataAssets>,
+    weapon_defs: Res<Assets<WeaponDefinitions>>,
+    current_level: Res<CurrentLevel>,
+    levels: Res<Assets<Level>>,
 ) {
-    let gun = commands.spawn_bundle(SpriteBundle {
-        transform: Transform::from_xyz(1., -1., 1.),
-        texture: texture.basic_gun.clone(),
-        ..default()
-    })
-        .insert(Name::new("Gun"))
-        .insert(Gun(6, 6, BulletType::Basic))
-        .insert(Ammo(36))
-        .insert(UsingGun)
-        .insert(Belt(0))
-        .id();
-    let rocket = commands.spawn_bundle(SpriteBundle {
-        transform: Transform::from_xyz(2., 0., 1.),
-        texture: texture.rocket_gun.clone(),
-        visibility: Visibility {
-            is_visible: false
-        },
-        ..default()
-    })
-        .insert(Name::new("Rocket Gun"))
-        .insert(Gun(1, 1, BulletType::Rocket))
-        .insert(Ammo(10))
-        .insert(Belt(1))
-        .id();
+    let weapons = weapon_defs.get(&data.weapons).unwrap();
+    let position = levels.get(&current_level.0)
+        .map(|level| level.tile_center(level.player_spawn))
+        .unwrap_or(Vec2::ZERO);
+    let guns = weapons.weapons.iter().enumerate().map(|(i, weapon)| {
+        commands.spawn_bundle(SpriteSheetBundle {
+            transform: Transform::from_xyz(weapon.offset.0, weapon.offset.1, 1.),
+            visibility: Visibility {
+                is_visible: i == 0
+            },
+            ..default()
+        })
+            .insert(Animator::new(weapon.gun_clips.clone(), IDLE))
+            .insert(Name::new(weapon.name.clone()))
+            .insert(Gun(weapon.magazine, weapon.magazine, weapon.id.clone()))
+            .insert(Ammo(weapon.reserve))
+            .insert(FireControl::default())
+            .insert(Belt(i as i8))
+            .id()
+    }).collect::<Vec<_>>();
+    if let Some(first) = guns.first() {
+        commands.entity(*first).insert(UsingGun);
+    }
     commands.spawn_bundle(PlayerBundle {
-        texture: texture.player.clone(),
+        transform: Transform::from_translation(position.extend(0.5)),
+        hitbox: Hitbox::Aabb(Vec2::new(7., 15.)),
         motion: Motion::new(0.1, 0.1),
         ..default()
     })
         .insert(GameEntity)
         .insert(Player)
+        .insert(Aim(Vec2::X))
+        .insert(Animator::new(animations.player.clone(), IDLE))
         .insert(Name::new("Player"))
         .insert(Belt(0))
-        .push_children(&[gun, rocket]);
+        .push_children(&guns);
 }
 
 fn despawn_player(
@@ -132,35 +175,150 @@ fn despawn_player(
     }
 }
 
+fn player_death(
+    mut death_events: EventReader<DeathEvent>,
+    mut app_state: ResMut<State<AppState>>,
+    mut q_player: Query<&mut Animator, With<Player>>,
+    mut q_gun: Query<&mut Visibility, With<UsingGun>>,
+) {
+    for event in death_events.iter() {
+        if let Ok(mut animator) = q_player.get_mut(event.entity) {
+            animator.restart(DIE);
+            // drops the gun
+            for mut vis in q_gun.iter_mut() {
+                vis.is_visible = false;
+            }
+            // overwrites a pause queued this frame
+            let _ = app_state.overwrite_set(AppState::Game(GameState::GameOver));
+            return;
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
 fn manual_reload(
-    keys: Res<Input<KeyCode>>,
-    mut q_gun: Query<(&mut Gun, &mut Ammo), With<UsingGun>>,
+    mut commands: Commands,
+    mut sound_events: EventWriter<SoundEvent>,
+    actions: Res<Input<Action>>,
+    data: Res<DataAssets>,
+    weapon_defs: Res<Assets<WeaponDefinitions>>,
+    q_gun: Query<(Entity, &GlobalTransform, &Gun, &Ammo), (With<UsingGun>, Without<Reloading>)>,
 ) {
-    if keys.just_pressed(KeyCode::R) {
-        let (mut gun, mut ammo) = q_gun.single_mut();
+    if actions.just_pressed(Action::Reload) {
+        let (ent, g_tr, gun, ammo) = match q_gun.get_single() {
+            Ok(g) => g,
+            Err(_) => return,
+        };
+        let weapon = match weapon_defs.get(&data.weapons).and_then(|w| w.get(&gun.2)) {
+            Some(w) => w,
+            None => return,
+        };
+        let position = g_tr.translation().truncate();
         if ammo.0 == 0 {
-            // TODO: play sound
+            play_sound(&mut sound_events, &weapon.empty_audio, position);
             return;
         }
-        gun.reload(&mut ammo)
+        start_reload(&mut commands, &mut sound_events, ent, gun, ammo, weapon, position);
+    }
+}
+
+fn start_reload(
+    commands: &mut Commands,
+    sound_events: &mut EventWriter<SoundEvent>,
+    ent: Entity,
+    gun: &Gun,
+    ammo: &Ammo,
+    weapon: &WeaponDefinition,
+    position: Vec2,
+) {
+    if !gun.can_reload(ammo) {
+        return;
+    }
+    commands.entity(ent).insert(Reloading {
+        timer: Timer::from_seconds(weapon.reload_time, false),
+        interrupted: false,
+    });
+    play_sound(sound_events, &weapon.reload_audio, position);
+}
+
+fn reload_gun(
+    mut commands: Commands,
+    mut sound_events: EventWriter<SoundEvent>,
+    time: Res<Time>,
+    data: Res<DataAssets>,
+    weapon_defs: Res<Assets<WeaponDefinitions>>,
+    mut q_gun: Query<(Entity, &mut Gun, &mut Ammo, &mut Reloading, &mut Transform, &GlobalTransform)>,
+) {
+    let weapons = match weapon_defs.get(&data.weapons) {
+        Some(w) => w,
+        None => return,
+    };
+    for (ent, mut gun, mut ammo, mut reloading, mut tf, g_tr) in q_gun.iter_mut() {
+        let weapon = match weapons.get(&gun.2) {
+            Some(w) => w,
+            None => continue,
+        };
+        reloading.timer.tick(time.delta());
+        animate_reload(&mut tf, &reloading, weapon);
+        if !reloading.timer.finished() {
+            continue;
+        }
+        if weapon.per_shell {
+            gun.load_shell(&mut ammo);
+            if gun.can_reload(&ammo) && !reloading.interrupted {
+                reloading.timer.reset();
+                // every shell makes its own sound
+                play_sound(&mut sound_events, &weapon.reload_audio, g_tr.translation().truncate());
+                continue;
+            }
+        } else {
+            gun.reload(&mut ammo);
+        }
+        end_reload(&mut commands, ent, &mut tf, weapon);
     }
 }
 
+fn animate_reload(tf: &mut Transform, reloading: &Reloading, weapon: &WeaponDefinition) {
+    let progress = reloading.progress();
+    match weapon.reload_animation {
+        ReloadAnimation::Dip => {
+            tf.translation.y = weapon.offset.1 - 2. * (progress * PI).sin();
+        }
+        ReloadAnimation::Spin => {
+            // on top of the aim rotation `move_gun` sets every frame
+            tf.rotation *= Quat::from_rotation_z(progress * TAU);
+        }
+    }
+}
+
+fn end_reload(
+    commands: &mut Commands,
+    ent: Entity,
+    tf: &mut Transform,
+    weapon: &WeaponDefinition,
+) {
+    tf.translation.y = weapon.offset.1;
+    commands.entity(ent).remove::<Reloading>();
+}
+
 // TODO: update rotation
+#[allow(clippy::type_complexity)]
 fn change_gun(
     mut commands: Commands,
-    keys: Res<Input<KeyCode>>,
-    mut q_gun: Query<(&mut Visibility, Entity, &Belt), With<Gun>>,
+    actions: Res<Input<Action>>,
+    data: Res<DataAssets>,
+    weapon_defs: Res<Assets<WeaponDefinitions>>,
+    mut q_gun: Query<(&mut Visibility, Entity, &Belt, &Gun, Option<&Reloading>, &mut Transform), With<Gun>>,
     mut q_belt: Query<&mut Belt, (With<Player>, Without<Gun>)>,
 ) {
     let mut belt = q_belt.single_mut();
-    if keys.just_pressed(KeyCode::E) {
+    if actions.just_pressed(Action::NextWeapon) {
         belt.0 += 1;
-    } else if keys.just_pressed(KeyCode::Q) {
+    } else if actions.just_pressed(Action::PreviousWeapon) {
         belt.0 -= 1;
     }
     belt.0 = belt.0.clamp(0, q_gun.iter().collect::<Vec<_>>().len() as i8 - 1);
-    for (mut vis, ent, gun_belt) in q_gun.iter_mut() {
+    for (mut vis, ent, gun_belt, gun, reloading, mut tf) in q_gun.iter_mut() {
         if belt.0 == gun_belt.0 {
             commands
                 .entity(ent)
@@ -171,40 +329,43 @@ fn change_gun(
                 .entity(ent)
                 .remove::<UsingGun>();
             vis.is_visible = false;
+            // switching away cancels the reload
+            let weapon = weapon_defs.get(&data.weapons).and_then(|w| w.get(&gun.2));
+            if let (Some(_), Some(weapon)) = (reloading, weapon) {
+                end_reload(&mut commands, ent, &mut tf, weapon);
+            }
         }
     }
 }
 
 fn control_player(
     mut q_motion: Query<(&mut Motion, &Controllable), With<Player>>,
-    keys: Res<Input<KeyCode>>,
+    actions: Res<Input<Action>>,
+    sticks: Res<GamepadSticks>,
     time: Res<Time>,
 ) {
     let delta = time.delta_seconds() * 100.0;
+    // keys give -1, 0 or 1 per axis, the stick anything in between
+    let axis = |negative: Action, positive: Action, stick: f32| {
+        let keys = actions.pressed(positive) as i8 - actions.pressed(negative) as i8;
+        (keys as f32 + stick).clamp(-1., 1.)
+    };
+    let input = Vec2::new(
+        axis(Action::MoveLeft, Action::MoveRight, sticks.movement.x),
+        axis(Action::MoveDown, Action::MoveUp, sticks.movement.y),
+    );
     for (mut motion, cont) in q_motion.iter_mut() {
         if !cont.is_controllable { return; }
-        let key_left = keys.pressed(KeyCode::A);
-        let key_right = keys.pressed(KeyCode::D);
-        let key_up = keys.pressed(KeyCode::W);
-        let key_down = keys.pressed(KeyCode::S);
         // up down
-        if key_down {
-            motion.speed.y -= motion.acc * delta;
-        }
-        if key_up {
-            motion.speed.y += motion.acc * delta;
-        }
-        if (key_down && key_up) || (!key_down && !key_up) {
+        if input.y != 0. {
+            motion.speed.y += motion.acc * delta * input.y;
+        } else {
             motion.speed.y -= motion.speed.y * motion.dcc * delta.clamp(0.0, 0.9);
         }
         // left right
-        if key_left {
-            motion.speed.x -= motion.acc * delta;
-        }
-        if key_right {
-            motion.speed.x += motion.acc * delta;
-        }
-        if (key_left && key_right) || (!key_left && !key_right) {
+        if input.x != 0. {
+            motion.speed.x += motion.acc * delta * input.x;
+        } else {
             motion.speed.x -= motion.speed.x * motion.dcc * delta.clamp(0.0, 0.9);
         }
         motion.speed.y = motion.speed.y.clamp(-3., 3.);
@@ -212,118 +373,182 @@ fn control_player(
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn move_gun(
-    mut cursor_event_reader: EventReader<CursorMoved>,
-    mut windows: ResMut<Windows>,
-    mut q_gun: Query<&mut Transform, With<UsingGun>>,
-    mut q_pl: Query<&mut Sprite, Or<(&UsingGun, &Player)>>,
+    windows: Res<Windows>,
+    sticks: Res<GamepadSticks>,
+    data: Res<DataAssets>,
+    weapon_defs: Res<Assets<WeaponDefinitions>>,
+    q_camera: Query<(&Transform, &OrthographicProjection), With<Camera2d>>,
+    mut q_player: Query<(&Transform, &mut Aim, &mut TextureAtlasSprite), (With<Player>, Without<Camera2d>)>,
+    mut q_gun: Query<(&mut Transform, &mut TextureAtlasSprite, &Gun, &FireControl), (With<UsingGun>, Without<Player>, Without<Camera2d>)>,
 ) {
-    let mouse = match cursor_event_reader.iter().next() {
-        None => return,
-        Some(s) => s,
-    };
-    let mut gun_t = match q_gun.get_single_mut() {
-        Ok(g) => g,
+    let (player_tf, mut aim, mut player_sprite) = match q_player.get_single_mut() {
+        Ok(p) => p,
         Err(_) => return,
     };
-    let window = match windows.get_primary_mut() {
-        None => return,
-        Some(s) => s,
-    };
-    let pos_x = mouse.position.x - window.width() / 2.;
-    let pos_y = mouse.position.y - window.height() / 2.;
-    let rotation = (pos_y / pos_x).atan();
-    gun_t.rotation = Quat::from_rotation_z(rotation);
-    for mut sprite in q_pl.iter_mut() {
-        if pos_x < 0. {
-            sprite.flip_x = true;
-            if gun_t.translation.x > 0. {
-                gun_t.translation.x = -gun_t.translation.x;
+    match sticks.last_device {
+        InputDevice::Gamepad => {
+            // releasing the stick keeps the last aim
+            if sticks.aim != Vec2::ZERO {
+                aim.0 = sticks.aim.normalize();
             }
-        } else {
-            sprite.flip_x = false;
-            if gun_t.translation.x < 0. {
-                gun_t.translation.x = -gun_t.translation.x;
+        }
+        InputDevice::MouseKeyboard => {
+            let cursor = windows.get_primary().zip(q_camera.get_single().ok())
+                .and_then(|(window, (cam_tf, projection))| cursor_to_world(window, cam_tf, projection));
+            if let Some(cursor) = cursor {
+                let dir = cursor - player_tf.translation.truncate();
+                if dir.length_squared() > f32::EPSILON {
+                    aim.0 = dir.normalize();
+                }
             }
         }
     }
+    let left = aim.0.x < 0.;
+    player_sprite.flip_x = left;
+    if let Ok((mut gun_tf, mut gun_sprite, gun, fire)) = q_gun.get_single_mut() {
+        let weapon = match weapon_defs.get(&data.weapons).and_then(|w| w.get(&gun.2)) {
+            Some(w) => w,
+            None => return,
+        };
+        gun_tf.rotation = Quat::from_rotation_z(aim.0.y.atan2(aim.0.x));
+        // rotated past 90 degrees the gun would be upside down
+        gun_sprite.flip_y = left;
+        let offset = Vec2::new(if left { -weapon.offset.0 } else { weapon.offset.0 }, weapon.offset.1);
+        // kicked back against the aim, the reload animation moves it on top of this
+        let position = offset - aim.0 * fire.kick;
+        gun_tf.translation.x = position.x;
+        gun_tf.translation.y = position.y;
+    }
 }
 
-fn shoot(
-    mut commands: Commands,
-    texture: Res<TextureAssets>,
-    input: Res<Input<MouseButton>>,
-    mut q_gun: Query<(&Transform, &GlobalTransform, &Sprite, &mut Gun, &mut Ammo), With<UsingGun>>,
+// bloom and gun kick wear off while not shooting
+fn recover_recoil(
+    data: Res<DataAssets>,
+    weapon_defs: Res<Assets<WeaponDefinitions>>,
+    time: Res<Time>,
+    mut q_gun: Query<(&Gun, &mut FireControl)>,
 ) {
-    if input.just_pressed(MouseButton::Left) {
-        let (tr, g_tr, spr, mut gun, mut ammo) = match q_gun.get_single_mut() {
-            Ok(g) => g,
-            Err(_) => return,
+    let weapons = match weapon_defs.get(&data.weapons) {
+        Some(w) => w,
+        None => return,
+    };
+    let delta = time.delta_seconds();
+    for (gun, mut fire) in q_gun.iter_mut() {
+        let weapon = match weapons.get(&gun.2) {
+            Some(w) => w,
+            None => continue,
         };
-        if gun.0 == 0 {
-            if ammo.0 == 0 {
-                // TODO: play sound
-                return;
-            }
-            gun.reload(&mut ammo);
-            return;
+        if fire.bloom > 0. {
+            fire.bloom = (fire.bloom - weapon.recoil.recovery * delta).max(0.);
+        }
+        if fire.kick > 0. {
+            fire.kick -= fire.kick * (KICK_RECOVERY * delta).min(1.);
         }
-        gun.0 -= 1;
-        let texture = match gun.2 {
-            BulletType::Basic => texture.basic_bullet.clone(),
-            BulletType::Rocket => texture.rocket_bullet.clone(),
-        };
-        commands.spawn_bundle(SpriteBundle {
-            transform: Transform {
-                translation: g_tr.translation() - Vec3::new(0., 0., 1.),
-                rotation: tr.rotation,
-                ..default()
-            },
-            sprite: Sprite {
-                flip_x: spr.flip_x,
-                ..default()
-            },
-            texture,
-            ..default()
-        })
-            .insert(Bullet(tr.translation.x, gun.2.clone(), g_tr.translation()));
     }
 }
 
-fn move_bullet(
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn shoot(
     mut commands: Commands,
-    mut q_bullet: Query<(&mut Transform, &Bullet, Entity)>,
+    mut noise_events: EventWriter<NoiseEvent>,
+    mut sound_events: EventWriter<SoundEvent>,
+    mut shot_events: EventWriter<ShotEvent>,
+    data: Res<DataAssets>,
+    weapon_defs: Res<Assets<WeaponDefinitions>>,
+    actions: Res<Input<Action>>,
+    time: Res<Time>,
+    mut q_gun: Query<(Entity, &GlobalTransform, &mut Gun, &Ammo, &mut FireControl, Option<&mut Reloading>), With<UsingGun>>,
+    mut q_player: Query<(Entity, &Aim, &mut Motion), With<Player>>,
 ) {
-    for (mut tf, bt, ent) in q_bullet.iter_mut() {
-        let rot = tf.rotation.to_euler(EulerRot::XYZ).2;
-        tf.translation.x += bt.0 * bt.speed() * rot.cos();
-        tf.translation.y += bt.0 * bt.speed() * rot.sin();
-        match bt.1 {
-            BulletType::Basic => {
-                if bt.2.distance(tf.translation) > 1000. {
-                    commands.entity(ent).despawn();
-                }
+    let (ent, g_tr, mut gun, ammo, mut fire, reloading) = match q_gun.get_single_mut() {
+        Ok(g) => g,
+        Err(_) => return,
+    };
+    let (player, aim, mut motion) = match q_player.get_single_mut() {
+        Ok((p, a, m)) => (p, a.0, m),
+        Err(_) => return,
+    };
+    let weapon = match weapon_defs.get(&data.weapons).and_then(|w| w.get(&gun.2)) {
+        Some(w) => w,
+        None => return,
+    };
+    fire.cooldown = (fire.cooldown - time.delta_seconds()).max(0.);
+    let pressed = actions.pressed(Action::Fire);
+    let wants_fire = match weapon.fire_mode {
+        FireMode::Semi => actions.just_pressed(Action::Fire),
+        FireMode::Automatic => pressed,
+        FireMode::Burst(rounds) => {
+            if actions.just_pressed(Action::Fire) && fire.burst_left == 0 {
+                fire.burst_left = rounds;
             }
-            BulletType::Rocket => {
-                if bt.2.distance(tf.translation) > 4000. {
-                    commands.entity(ent).despawn();
-                }
+            fire.burst_left > 0
+        }
+        FireMode::Charge(charge_time) => {
+            if pressed {
+                fire.charge += time.delta_seconds();
+            }
+            let released = actions.just_released(Action::Fire) && fire.charge >= charge_time;
+            if !pressed {
+                fire.charge = 0.;
             }
+            released
+        }
+    };
+    if !wants_fire || fire.cooldown > 0. {
+        return;
+    }
+    if let Some(mut reloading) = reloading {
+        // a shell by shell reload can be stopped, the rest has to finish first
+        if weapon.per_shell && gun.0 > 0 {
+            reloading.interrupted = true;
+        }
+        fire.burst_left = 0;
+        return;
+    }
+    if gun.0 == 0 {
+        fire.burst_left = 0;
+        if ammo.0 == 0 {
+            play_sound(&mut sound_events, &weapon.empty_audio, g_tr.translation().truncate());
+            return;
         }
+        start_reload(&mut commands, &mut sound_events, ent, &gun, ammo, weapon, g_tr.translation().truncate());
+        return;
     }
+    gun.0 -= 1;
+    fire.cooldown = 1. / weapon.fire_rate;
+    fire.burst_left = fire.burst_left.saturating_sub(1);
+    fire_weapon(&mut commands, weapon, g_tr.translation(), aim, fire.bloom, Team::Player, Some(player));
+    let recoil = &weapon.recoil;
+    fire.bloom = (fire.bloom + recoil.bloom).min(recoil.max_bloom);
+    fire.kick = recoil.kick;
+    motion.speed -= aim * recoil.push;
+    play_sound(&mut sound_events, &weapon.fire_audio, g_tr.translation().truncate());
+    shot_events.send(ShotEvent {
+        shooter: player,
+        gun: Some(ent),
+        weapon: weapon.id.clone(),
+    });
+    noise_events.send(NoiseEvent {
+        position: g_tr.translation().truncate(),
+        radius: weapon.bullet.noise_radius(),
+    });
 }
 
 #[derive(Bundle)]
 pub struct PlayerBundle {
-    pub sprite: Sprite,
+    pub sprite: TextureAtlasSprite,
     pub transform: Transform,
     pub global_transform: GlobalTransform,
-    pub texture: Handle<Image>,
+    pub texture_atlas: Handle<TextureAtlas>,
     pub hitbox: Hitbox,
     pub visibility: Visibility,
     pub computed_visibility: ComputedVisibility,
     pub controllable: Controllable,
     pub motion: Motion,
+    pub health: Health,
+    pub team: Team,
 }
 
 impl Default for PlayerBundle {
@@ -332,7 +557,7 @@ impl Default for PlayerBundle {
             sprite: Default::default(),
             transform: Default::default(),
             global_transform: Default::default(),
-            texture: DEFAULT_IMAGE_HANDLE.typed(),
+            texture_atlas: Default::default(),
             hitbox: Default::default(),
             visibility: Default::default(),
             computed_visibility: Default::default(),
@@ -340,6 +565,8 @@ impl Default for PlayerBundle {
                 is_controllable: true
             },
             motion: Default::default(),
+            health: Health::new(100),
+            team: Team::Player,
         }
     }
 }
\ No newline at end of file
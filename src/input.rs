@@ -0,0 +1,367 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const BINDINGS_PATH: &str = "config/bindings.ron";
+
+// what gameplay systems read instead of raw keys, through `Res<Input<Action>>`
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Fire,
+    Reload,
+    NextWeapon,
+    PreviousWeapon,
+    ZoomIn,
+    ZoomOut,
+    Pause,
+}
+
+impl Action {
+    pub const ALL: [Action; 11] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Fire,
+        Action::Reload,
+        Action::NextWeapon,
+        Action::PreviousWeapon,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::Pause,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Fire => "Fire",
+            Action::Reload => "Reload",
+            Action::NextWeapon => "Next weapon",
+            Action::PreviousWeapon => "Previous weapon",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+            Action::Pause => "Pause",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Bindings {
+    pub actions: BTreeMap<Action, Vec<Binding>>,
+    // stick input below this length is ignored, the rest is rescaled to 0..1
+    #[serde(default = "default_dead_zone")]
+    pub dead_zone: f32,
+}
+
+fn default_dead_zone() -> f32 {
+    0.2
+}
+
+// files saved before the dead zone setting were just the map, they still load with the default dead zone
+#[derive(Deserialize)]
+struct MapOnlyBindings(BTreeMap<Action, Vec<Binding>>);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let mut map = BTreeMap::new();
+        map.insert(Action::MoveUp, vec![Binding::Key(KeyCode::W)]);
+        map.insert(Action::MoveDown, vec![Binding::Key(KeyCode::S)]);
+        map.insert(Action::MoveLeft, vec![Binding::Key(KeyCode::A)]);
+        map.insert(Action::MoveRight, vec![Binding::Key(KeyCode::D)]);
+        map.insert(Action::Fire, vec![
+            Binding::Mouse(MouseButton::Left),
+            Binding::Gamepad(GamepadButtonType::RightTrigger2),
+        ]);
+        map.insert(Action::Reload, vec![
+            Binding::Key(KeyCode::R),
+            Binding::Gamepad(GamepadButtonType::West),
+        ]);
+        map.insert(Action::NextWeapon, vec![
+            Binding::Key(KeyCode::E),
+            Binding::Gamepad(GamepadButtonType::RightTrigger),
+        ]);
+        map.insert(Action::PreviousWeapon, vec![
+            Binding::Key(KeyCode::Q),
+            Binding::Gamepad(GamepadButtonType::LeftTrigger),
+        ]);
+        map.insert(Action::ZoomIn, vec![
+            Binding::Key(KeyCode::Equals),
+            Binding::Gamepad(GamepadButtonType::DPadUp),
+        ]);
+        map.insert(Action::ZoomOut, vec![
+            Binding::Key(KeyCode::Minus),
+            Binding::Gamepad(GamepadButtonType::DPadDown),
+        ]);
+        map.insert(Action::Pause, vec![
+            Binding::Key(KeyCode::Escape),
+            Binding::Gamepad(GamepadButtonType::Start),
+        ]);
+        Self {
+            actions: map,
+            dead_zone: default_dead_zone(),
+        }
+    }
+}
+
+impl Bindings {
+    // falls back to the defaults when the file is missing or broken, missing actions get their default
+    pub fn load() -> Self {
+        let mut bindings = fs::read_to_string(BINDINGS_PATH)
+            .ok()
+            .and_then(|file| match Bindings::parse(&file) {
+                Ok(b) => Some(b),
+                Err(e) => {
+                    warn!("couldn't parse {}: {}", BINDINGS_PATH, e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        for (action, default) in Bindings::default().actions {
+            bindings.actions.entry(action).or_insert(default);
+        }
+        bindings
+    }
+
+    fn parse(file: &str) -> ron::Result<Self> {
+        ron::from_str::<Bindings>(file).or_else(|e| match ron::from_str::<MapOnlyBindings>(file) {
+            Ok(old) => Ok(Bindings {
+                actions: old.0,
+                dead_zone: default_dead_zone(),
+            }),
+            Err(_) => Err(e),
+        })
+    }
+
+    pub fn save(&self) {
+        let file = match ron::ser::to_string_pretty(self, Default::default()) {
+            Ok(f) => f,
+            Err(e) => {
+                warn!("couldn't serialize bindings: {}", e);
+                return;
+            }
+        };
+        if let Some(dir) = Path::new(BINDINGS_PATH).parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let Err(e) = fs::write(BINDINGS_PATH, file) {
+            warn!("couldn't save {}: {}", BINDINGS_PATH, e);
+        }
+    }
+
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.actions.get(&action).map(|b| b.as_slice()).unwrap_or_default()
+    }
+
+    // replaces the bindings of the same device, so rebinding a key keeps the gamepad button
+    pub fn rebind(&mut self, action: Action, binding: Binding) {
+        let bindings = self.actions.entry(action).or_default();
+        bindings.retain(|b| b.is_gamepad() != binding.is_gamepad());
+        bindings.push(binding);
+    }
+}
+
+impl Binding {
+    pub fn is_gamepad(&self) -> bool {
+        matches!(self, Binding::Gamepad(_))
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{:?}", key),
+            Binding::Mouse(button) => format!("Mouse {:?}", button),
+            Binding::Gamepad(button) => format!("Pad {:?}", button),
+        }
+    }
+}
+
+// when set, the next pressed key, mouse or gamepad button becomes the action's binding
+#[derive(Default)]
+pub struct Rebinding(pub Option<Action>);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputDevice {
+    MouseKeyboard,
+    Gamepad,
+}
+
+// stick positions of the first connected gamepad with the dead zone already applied
+pub struct GamepadSticks {
+    pub movement: Vec2,
+    pub aim: Vec2,
+    // whichever device was used last, decides if the gun follows the mouse or the right stick
+    pub last_device: InputDevice,
+}
+
+impl Default for GamepadSticks {
+    fn default() -> Self {
+        Self {
+            movement: Vec2::ZERO,
+            aim: Vec2::ZERO,
+            last_device: InputDevice::MouseKeyboard,
+        }
+    }
+}
+
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Bindings::load());
+        app.init_resource::<Input<Action>>();
+        app.init_resource::<Rebinding>();
+        app.init_resource::<GamepadSticks>();
+        app.add_system_to_stage(CoreStage::PreUpdate, update_sticks.after(InputSystem));
+        app.add_system_to_stage(CoreStage::PreUpdate, capture_rebinding.label("rebinding").after(InputSystem));
+        app.add_system_to_stage(CoreStage::PreUpdate, update_actions.after("rebinding"));
+    }
+}
+
+fn update_actions(
+    bindings: Res<Bindings>,
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    mut actions: ResMut<Input<Action>>,
+) {
+    let gamepad = gamepads.iter().next().cloned();
+    actions.clear();
+    for action in Action::ALL {
+        let down = bindings.get(action).iter().any(|binding| match binding {
+            Binding::Key(key) => keys.pressed(*key),
+            Binding::Mouse(button) => mouse.pressed(*button),
+            Binding::Gamepad(button) => gamepad
+                .map(|g| gamepad_buttons.pressed(GamepadButton::new(g, *button)))
+                .unwrap_or(false),
+        });
+        if down && !actions.pressed(action) {
+            actions.press(action);
+        } else if !down && actions.pressed(action) {
+            actions.release(action);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_sticks(
+    bindings: Res<Bindings>,
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    mut cursor_events: EventReader<CursorMoved>,
+    mut sticks: ResMut<GamepadSticks>,
+) {
+    let stick = |gamepad: Gamepad, x: GamepadAxisType, y: GamepadAxisType| {
+        let raw = Vec2::new(
+            axes.get(GamepadAxis::new(gamepad, x)).unwrap_or(0.),
+            axes.get(GamepadAxis::new(gamepad, y)).unwrap_or(0.),
+        );
+        apply_dead_zone(raw, bindings.dead_zone)
+    };
+    let (movement, aim) = match gamepads.iter().next().cloned() {
+        Some(gamepad) => (
+            stick(gamepad, GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY),
+            stick(gamepad, GamepadAxisType::RightStickX, GamepadAxisType::RightStickY),
+        ),
+        None => (Vec2::ZERO, Vec2::ZERO),
+    };
+    sticks.movement = movement;
+    sticks.aim = aim;
+    if movement != Vec2::ZERO || aim != Vec2::ZERO || gamepad_buttons.get_just_pressed().next().is_some() {
+        sticks.last_device = InputDevice::Gamepad;
+    } else if cursor_events.iter().next().is_some()
+        || keys.get_just_pressed().next().is_some()
+        || mouse.get_just_pressed().next().is_some() {
+        sticks.last_device = InputDevice::MouseKeyboard;
+    }
+}
+
+// radial dead zone, the remaining range is stretched back to 0..1
+fn apply_dead_zone(stick: Vec2, dead_zone: f32) -> Vec2 {
+    let length = stick.length();
+    if length <= dead_zone || dead_zone >= 1. {
+        return Vec2::ZERO;
+    }
+    let scaled = ((length - dead_zone) / (1. - dead_zone)).min(1.);
+    stick / length * scaled
+}
+
+fn capture_rebinding(
+    mut rebinding: ResMut<Rebinding>,
+    mut bindings: ResMut<Bindings>,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut mouse: ResMut<Input<MouseButton>>,
+    mut gamepad_buttons: ResMut<Input<GamepadButton>>,
+) {
+    let action = match rebinding.0 {
+        Some(a) => a,
+        None => return,
+    };
+    // the captured input is reset so it doesn't also trigger its action this frame
+    // escape cancels instead of binding
+    if keys.just_pressed(KeyCode::Escape) {
+        keys.reset(KeyCode::Escape);
+        rebinding.0 = None;
+        return;
+    }
+    let key = keys.get_just_pressed().next().copied();
+    let button = mouse.get_just_pressed().next().copied();
+    let gamepad_button = gamepad_buttons.get_just_pressed().next().copied();
+    let binding = if let Some(key) = key {
+        keys.reset(key);
+        Binding::Key(key)
+    } else if let Some(button) = button {
+        mouse.reset(button);
+        Binding::Mouse(button)
+    } else if let Some(button) = gamepad_button {
+        gamepad_buttons.reset(button);
+        Binding::Gamepad(button.button_type)
+    } else {
+        return;
+    };
+    bindings.rebind(action, binding);
+    bindings.save();
+    rebinding.0 = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_both_file_shapes() {
+        let old = "({MoveUp: [Key(Up)], Fire: [Mouse(Right)]})";
+        let bindings = Bindings::parse(old).unwrap();
+        assert_eq!(bindings.get(Action::MoveUp), &[Binding::Key(KeyCode::Up)]);
+        assert_eq!(bindings.dead_zone, default_dead_zone());
+
+        let mut bindings = Bindings {
+            dead_zone: 0.35,
+            ..default()
+        };
+        bindings.rebind(Action::Reload, Binding::Key(KeyCode::T));
+        let file = ron::ser::to_string_pretty(&bindings, Default::default()).unwrap();
+        let parsed = Bindings::parse(&file).unwrap();
+        assert_eq!(parsed.dead_zone, 0.35);
+        assert_eq!(parsed.get(Action::Reload), bindings.get(Action::Reload));
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::VecDeque;
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use crate::entity::pickup::{Pickup, PickupKind};
+use crate::level::{EnemySpawnPoint, Level, BONES, FLOOR, FLOOR_CRACKED, MOSS, WALL};
+
+const WIDTH: u32 = 48;
+const HEIGHT: u32 = 36;
+// chance of a cell starting as a wall
+const FILL: f64 = 0.45;
+const SMOOTHING_STEPS: u32 = 5;
+const ENEMY_SPAWNS: usize = 6;
+const PICKUPS: usize = 4;
+// spawners closer to the player start than this, in tiles, are rejected while there are others
+const MIN_SPAWN_DISTANCE: u32 = 12;
+
+// cellular automata cave, the same seed always gives the same level
+pub fn generate(seed: u64, tileset: Handle<Image>, tile_size: f32) -> Level {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut walls = random_fill(&mut rng);
+    for _ in 0..SMOOTHING_STEPS {
+        walls = smooth(&walls);
+    }
+    let center = UVec2::new(WIDTH / 2, HEIGHT / 2);
+    // everything outside the biggest cave gets filled in, so every floor cell is reachable
+    let mut cave = largest_region(&walls);
+    if cave.is_empty() {
+        cave.push((center.y * WIDTH + center.x) as usize);
+    }
+    walls = vec![true; walls.len()];
+    for i in cave.iter() {
+        walls[*i] = false;
+    }
+
+    let cells = (WIDTH * HEIGHT) as usize;
+    let mut level = Level {
+        name: format!("Cave {}", seed),
+        size: UVec2::new(WIDTH, HEIGHT),
+        tile_size,
+        tileset,
+        floor: vec![None; cells],
+        walls: vec![None; cells],
+        decoration: vec![None; cells],
+        player_spawn: UVec2::ZERO,
+        enemy_spawns: Vec::new(),
+        pickups: Vec::new(),
+        markers: Vec::new(),
+    };
+    for (i, wall) in walls.iter().enumerate() {
+        if *wall {
+            level.walls[i] = Some(WALL);
+            continue;
+        }
+        level.floor[i] = Some(if rng.gen_bool(0.1) { FLOOR_CRACKED } else { FLOOR });
+        if rng.gen_bool(0.03) {
+            level.decoration[i] = Some(if rng.gen_bool(0.5) { BONES } else { MOSS });
+        }
+    }
+
+    // player starts in the open cell closest to the middle
+    let start = cave.iter()
+        .copied()
+        .filter(|i| neighbour_walls(&walls, *i) == 0)
+        .min_by_key(|i| distance(tile(*i), center))
+        .unwrap_or(cave[0]);
+    level.player_spawn = tile(start);
+
+    let distances = flood_distances(&walls, start);
+    let mut candidates: Vec<usize> = cave.iter().copied().filter(|i| *i != start).collect();
+    candidates.shuffle(&mut rng);
+    let (far, near): (Vec<usize>, Vec<usize>) = candidates.iter()
+        .copied()
+        .partition(|i| distances[*i] >= MIN_SPAWN_DISTANCE);
+    let spawners: Vec<usize> = far.into_iter().chain(near).take(ENEMY_SPAWNS).collect();
+    for i in spawners.iter() {
+        level.enemy_spawns.push(EnemySpawnPoint { tile: tile(*i), enemy: None });
+    }
+    for i in candidates.iter().filter(|i| !spawners.contains(i)).take(PICKUPS) {
+        let pickup = if rng.gen_bool(0.5) {
+            Pickup { kind: PickupKind::Health, amount: 25 }
+        } else {
+            Pickup { kind: PickupKind::Ammo, amount: 12 }
+        };
+        level.pickups.push((tile(*i), pickup));
+    }
+    level
+}
+
+fn tile(i: usize) -> UVec2 {
+    UVec2::new(i as u32 % WIDTH, i as u32 / WIDTH)
+}
+
+fn distance(a: UVec2, b: UVec2) -> u32 {
+    let d = a.as_ivec2() - b.as_ivec2();
+    (d.x.abs() + d.y.abs()) as u32
+}
+
+fn is_border(t: UVec2) -> bool {
+    t.x == 0 || t.y == 0 || t.x == WIDTH - 1 || t.y == HEIGHT - 1
+}
+
+fn random_fill(rng: &mut StdRng) -> Vec<bool> {
+    (0..(WIDTH * HEIGHT) as usize)
+        .map(|i| is_border(tile(i)) || rng.gen_bool(FILL))
+        .collect()
+}
+
+// walls among the 8 neighbours, outside the map counts as wall
+fn neighbour_walls(walls: &[bool], i: usize) -> u32 {
+    let t = tile(i).as_ivec2();
+    let mut count = 0;
+    for y in -1..=1 {
+        for x in -1..=1 {
+            if x == 0 && y == 0 {
+                continue;
+            }
+            let n = t + IVec2::new(x, y);
+            let outside = n.x < 0 || n.y < 0 || n.x >= WIDTH as i32 || n.y >= HEIGHT as i32;
+            if outside || walls[(n.y as u32 * WIDTH + n.x as u32) as usize] {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+// a cell becomes a wall with more than 4 wall neighbours and floor with less than 4
+fn smooth(walls: &[bool]) -> Vec<bool> {
+    (0..walls.len())
+        .map(|i| {
+            if is_border(tile(i)) {
+                return true;
+            }
+            match neighbour_walls(walls, i) {
+                n if n > 4 => true,
+                n if n < 4 => false,
+                _ => walls[i],
+            }
+        })
+        .collect()
+}
+
+fn open_neighbours(walls: &[bool], i: usize) -> impl Iterator<Item = usize> + '_ {
+    let t = tile(i);
+    [(-1, 0), (1, 0), (0, -1), (0, 1)]
+        .into_iter()
+        .map(move |(x, y)| t.as_ivec2() + IVec2::new(x, y))
+        .filter(|n| n.x >= 0 && n.y >= 0 && n.x < WIDTH as i32 && n.y < HEIGHT as i32)
+        .map(|n| (n.y as u32 * WIDTH + n.x as u32) as usize)
+        .filter(move |n| !walls[*n])
+}
+
+// floor cells of the biggest 4-connected floor area
+fn largest_region(walls: &[bool]) -> Vec<usize> {
+    let mut seen = vec![false; walls.len()];
+    let mut best = Vec::new();
+    for (start, wall) in walls.iter().enumerate() {
+        if *wall || seen[start] {
+            continue;
+        }
+        let mut region = vec![start];
+        seen[start] = true;
+        let mut next = 0;
+        while next < region.len() {
+            for n in open_neighbours(walls, region[next]) {
+                if !seen[n] {
+                    seen[n] = true;
+                    region.push(n);
+                }
+            }
+            next += 1;
+        }
+        if region.len() > best.len() {
+            best = region;
+        }
+    }
+    best
+}
+
+// steps from `start` to every floor cell, walls stay at u32::MAX
+fn flood_distances(walls: &[bool], start: usize) -> Vec<u32> {
+    let mut distances = vec![u32::MAX; walls.len()];
+    distances[start] = 0;
+    let mut queue = VecDeque::from([start]);
+    while let Some(i) = queue.pop_front() {
+        for n in open_neighbours(walls, i) {
+            if distances[n] == u32::MAX {
+                distances[n] = distances[i] + 1;
+                queue.push_back(n);
+            }
+        }
+    }
+    distances
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(t: UVec2) -> usize {
+        (t.y * WIDTH + t.x) as usize
+    }
+
+    #[test]
+    fn same_seed_gives_same_level() {
+        for seed in [0, 1, 42, 1234] {
+            let a = generate(seed, Handle::default(), 16.);
+            let b = generate(seed, Handle::default(), 16.);
+            assert_eq!(a.walls, b.walls);
+            assert_eq!(a.floor, b.floor);
+            assert_eq!(a.decoration, b.decoration);
+            assert_eq!(a.player_spawn, b.player_spawn);
+            let spawns = |l: &Level| l.enemy_spawns.iter().map(|s| s.tile).collect::<Vec<_>>();
+            assert_eq!(spawns(&a), spawns(&b));
+            let pickups = |l: &Level| l.pickups.iter().map(|(t, p)| (*t, p.amount)).collect::<Vec<_>>();
+            assert_eq!(pickups(&a), pickups(&b));
+        }
+    }
+
+    #[test]
+    fn spawns_and_pickups_are_reachable_from_player() {
+        for seed in 0..20 {
+            let level = generate(seed, Handle::default(), 16.);
+            let walls: Vec<bool> = level.walls.iter().map(|w| w.is_some()).collect();
+            let distances = flood_distances(&walls, index(level.player_spawn));
+            assert_eq!(level.enemy_spawns.len(), ENEMY_SPAWNS, "seed {}", seed);
+            for spawn in level.enemy_spawns.iter() {
+                assert_ne!(distances[index(spawn.tile)], u32::MAX, "seed {} spawn {}", seed, spawn.tile);
+            }
+            for (t, _) in level.pickups.iter() {
+                assert_ne!(distances[index(*t)], u32::MAX, "seed {} pickup {}", seed, t);
+            }
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use bevy::asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+use serde_json::Value;
+use crate::entity::enemy::EnemyType;
+use crate::entity::pickup::{Pickup, PickupKind};
+use crate::level::{EnemySpawnPoint, Level, LevelFields};
+
+// Imports LDtk projects (https://ldtk.io), every level of the project becomes a labeled `Level`
+// asset named after its identifier, e.g. `data/levels/crypt.ldtk#Crypt`, the first one is also
+// the default asset.
+//
+// Tile layers (Tiles, AutoLayer or IntGrid with auto rules) are matched by name:
+// "Walls" is solid, "Decoration" is drawn on top and anything else is floor.
+// All layers have to use the same tileset, laid out like `textures/tileset.png`.
+//
+// Entities:
+// - PlayerStart
+// - EnemySpawner, optional `enemy` enum field (Zombie, Skeleton, Mutant)
+// - Pickup, `kind` enum field (Health, Ammo) and `amount` int field
+// - anything else is spawned at its cell with a `LevelFields` component holding all of its fields,
+//   read with `LevelFields::get`
+#[derive(Default)]
+pub struct LdtkLoader;
+
+#[derive(Deserialize)]
+struct LdtkProject {
+    levels: Vec<LdtkLevel>,
+}
+
+#[derive(Deserialize)]
+struct LdtkLevel {
+    identifier: String,
+    // `None` when the project saves levels to separate files, which isn't supported
+    #[serde(rename = "layerInstances")]
+    layer_instances: Option<Vec<LdtkLayer>>,
+}
+
+#[derive(Deserialize)]
+struct LdtkLayer {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    #[serde(rename = "__cWid")]
+    width: u32,
+    #[serde(rename = "__cHei")]
+    height: u32,
+    #[serde(rename = "__gridSize")]
+    grid_size: u32,
+    // relative to the project file
+    #[serde(rename = "__tilesetRelPath")]
+    tileset: Option<String>,
+    #[serde(rename = "gridTiles", default)]
+    grid_tiles: Vec<LdtkTile>,
+    #[serde(rename = "autoLayerTiles", default)]
+    auto_layer_tiles: Vec<LdtkTile>,
+    #[serde(rename = "entityInstances", default)]
+    entities: Vec<LdtkEntity>,
+}
+
+#[derive(Deserialize)]
+struct LdtkTile {
+    // top left corner in pixels, y goes down
+    px: [u32; 2],
+    // tile index in the tileset
+    t: u32,
+}
+
+#[derive(Deserialize)]
+struct LdtkEntity {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    // cell, y goes down
+    #[serde(rename = "__grid")]
+    grid: [u32; 2],
+    #[serde(rename = "fieldInstances", default)]
+    fields: Vec<LdtkField>,
+}
+
+#[derive(Deserialize)]
+struct LdtkField {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    #[serde(rename = "__value")]
+    value: Value,
+}
+
+impl AssetLoader for LdtkLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let project: LdtkProject = serde_json::from_slice(bytes)?;
+            let directory = load_context.path().parent().map(|p| p.to_path_buf()).unwrap_or_default();
+            let mut first = true;
+            for ldtk_level in project.levels {
+                let layers = match ldtk_level.layer_instances {
+                    Some(l) => l,
+                    None => anyhow::bail!("level \"{}\" is saved in a separate file", ldtk_level.identifier),
+                };
+                let tileset = match layers.iter().find_map(|l| l.tileset.as_ref()) {
+                    Some(t) => AssetPath::new(directory.join(t), None),
+                    None => anyhow::bail!("level \"{}\" has no tileset", ldtk_level.identifier),
+                };
+                let level = convert_level(ldtk_level.identifier.clone(), &layers, load_context.get_handle(tileset.clone()))?;
+                if first {
+                    load_context.set_default_asset(LoadedAsset::new(level.clone()).with_dependencies(vec![tileset.clone()]));
+                    first = false;
+                }
+                load_context.set_labeled_asset(
+                    &ldtk_level.identifier,
+                    LoadedAsset::new(level).with_dependencies(vec![tileset]),
+                );
+            }
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ldtk"]
+    }
+}
+
+fn convert_level(name: String, layers: &[LdtkLayer], tileset: Handle<Image>) -> Result<Level, anyhow::Error> {
+    let width = layers.iter().map(|l| l.width).max().unwrap_or(0);
+    let height = layers.iter().map(|l| l.height).max().unwrap_or(0);
+    let tile_size = layers.iter().map(|l| l.grid_size).max().unwrap_or(0);
+    if width == 0 || height == 0 || tile_size == 0 {
+        anyhow::bail!("level \"{}\" is empty", name);
+    }
+    let cells = (width * height) as usize;
+    let mut level = Level {
+        name,
+        size: UVec2::new(width, height),
+        tile_size: tile_size as f32,
+        tileset,
+        floor: vec![None; cells],
+        walls: vec![None; cells],
+        decoration: vec![None; cells],
+        player_spawn: UVec2::ZERO,
+        enemy_spawns: Vec::new(),
+        pickups: Vec::new(),
+        markers: Vec::new(),
+    };
+    let mut player_spawn = None;
+    // layers are listed from the top one down, so lower layers are filled in first
+    for layer in layers.iter().rev() {
+        let cell = |x: u32, y: u32| -> Option<UVec2> {
+            (x < width && y < height).then(|| UVec2::new(x, height - 1 - y))
+        };
+        let target = match layer.identifier.to_lowercase().as_str() {
+            "walls" => &mut level.walls,
+            "decoration" => &mut level.decoration,
+            _ => &mut level.floor,
+        };
+        for tile in layer.grid_tiles.iter().chain(layer.auto_layer_tiles.iter()) {
+            if let Some(c) = cell(tile.px[0] / layer.grid_size, tile.px[1] / layer.grid_size) {
+                target[(c.y * width + c.x) as usize] = Some(tile.t);
+            }
+        }
+        for entity in layer.entities.iter() {
+            let c = match cell(entity.grid[0], entity.grid[1]) {
+                Some(c) => c,
+                None => continue,
+            };
+            let fields = LevelFields {
+                identifier: entity.identifier.clone(),
+                fields: entity.fields.iter()
+                    .map(|f| (f.identifier.clone(), f.value.clone()))
+                    .collect(),
+            };
+            match entity.identifier.as_str() {
+                "PlayerStart" => player_spawn = Some(c),
+                "EnemySpawner" => {
+                    let enemy = fields.get::<EnemyType>("enemy")?;
+                    level.enemy_spawns.push(EnemySpawnPoint { tile: c, enemy });
+                }
+                "Pickup" => {
+                    let kind = match fields.get::<PickupKind>("kind")? {
+                        Some(k) => k,
+                        None => anyhow::bail!("pickup at {}, {} has no kind", entity.grid[0], entity.grid[1]),
+                    };
+                    let amount = fields.get::<u32>("amount")?.unwrap_or(0);
+                    level.pickups.push((c, Pickup { kind, amount }));
+                }
+                _ => level.markers.push((c, fields)),
+            }
+        }
+    }
+    level.player_spawn = match player_spawn {
+        Some(p) => p,
+        None => anyhow::bail!("level \"{}\" has no PlayerStart", level.name),
+    };
+    Ok(level)
+}
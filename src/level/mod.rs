@@ -0,0 +1,481 @@
+pub(crate) mod ldtk;
+pub(crate) mod generator;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use bevy::asset::{AssetLoader, AssetPath, LoadContext, LoadState, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::render_resource::TextureUsages;
+use bevy::utils::BoxedFuture;
+use bevy_ecs_tilemap::prelude::*;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use crate::{AppState, GameState};
+use crate::asset_loader::{DataAssets, TextureAssets};
+use crate::collision::Solid;
+use crate::entity::Hitbox;
+use crate::entity::enemy::EnemyType;
+use crate::entity::pickup::{spawn_pickup, Pickup, PickupKind};
+use crate::level::ldtk::LdtkLoader;
+
+// tile indices in the tileset
+pub const FLOOR: u32 = 0;
+pub const FLOOR_CRACKED: u32 = 1;
+pub const WALL: u32 = 2;
+pub const BONES: u32 = 3;
+pub const MOSS: u32 = 4;
+
+// a level ready to be spawned, every layer is row-major starting at the bottom row
+#[derive(TypeUuid, Clone)]
+#[uuid = "9d2c51a4-7e3b-4f06-8a1d-c45e2b7f9063"]
+pub struct Level {
+    pub name: String,
+    pub size: UVec2,
+    pub tile_size: f32,
+    pub tileset: Handle<Image>,
+    // tile index per cell, `None` leaves the cell empty
+    pub floor: Vec<Option<u32>>,
+    pub walls: Vec<Option<u32>>,
+    pub decoration: Vec<Option<u32>>,
+    pub player_spawn: UVec2,
+    pub enemy_spawns: Vec<EnemySpawnPoint>,
+    pub pickups: Vec<(UVec2, Pickup)>,
+    // entities the game has no built-in meaning for, spawned as they are for gameplay code to pick up
+    pub markers: Vec<(UVec2, LevelFields)>,
+}
+
+// an editor entity and its custom fields, by field name
+#[derive(Component, Clone)]
+pub struct LevelFields {
+    pub identifier: String,
+    pub fields: HashMap<String, serde_json::Value>,
+}
+
+impl LevelFields {
+    // the field converted to `T`, `None` when it's missing or empty, an error when it's of another type
+    pub fn get<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>, serde_json::Error> {
+        match self.fields.get(name).filter(|v| !v.is_null()) {
+            Some(value) => serde_json::from_value(value.clone()).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct EnemySpawnPoint {
+    pub tile: UVec2,
+    // only this type spawns here, any type when `None`
+    pub enemy: Option<EnemyType>,
+}
+
+impl Level {
+    pub fn index(&self, tile: UVec2) -> usize {
+        (tile.y * self.size.x + tile.x) as usize
+    }
+
+    // world position of the bottom left corner, the level is centered on the origin
+    pub fn origin(&self) -> Vec2 {
+        -self.size.as_vec2() * self.tile_size / 2.
+    }
+
+    pub fn tile_center(&self, tile: UVec2) -> Vec2 {
+        self.origin() + (tile.as_vec2() + 0.5) * self.tile_size
+    }
+}
+
+// the level file as written by hand, rows go from top to bottom
+#[derive(Deserialize)]
+struct LevelFile {
+    name: String,
+    tileset: String,
+    tile_size: f32,
+    // '#' wall, '.' floor, ',' cracked floor, 'P' player start, 'E' enemy spawn,
+    // 'H' health pickup, 'A' ammo pickup, ' ' nothing
+    tiles: Vec<String>,
+    // 'b' bones, 'm' moss, anything else nothing
+    #[serde(default)]
+    decoration: Vec<String>,
+}
+
+impl LevelFile {
+    fn into_level(self, tileset: Handle<Image>) -> Result<Level, anyhow::Error> {
+        let height = self.tiles.len() as u32;
+        let width = self.tiles.iter().map(|row| row.chars().count()).max().unwrap_or(0) as u32;
+        if width == 0 || height == 0 {
+            anyhow::bail!("level \"{}\" has no tiles", self.name);
+        }
+        let cells = (width * height) as usize;
+        let mut level = Level {
+            name: self.name,
+            size: UVec2::new(width, height),
+            tile_size: self.tile_size,
+            tileset,
+            floor: vec![None; cells],
+            walls: vec![None; cells],
+            decoration: vec![None; cells],
+            player_spawn: UVec2::ZERO,
+            enemy_spawns: Vec::new(),
+            pickups: Vec::new(),
+            markers: Vec::new(),
+        };
+        let mut player_spawn = None;
+        for (row, line) in self.tiles.iter().enumerate() {
+            let y = height - 1 - row as u32;
+            for (x, c) in line.chars().enumerate() {
+                let tile = UVec2::new(x as u32, y);
+                let i = level.index(tile);
+                match c {
+                    '#' => level.walls[i] = Some(WALL),
+                    '.' => level.floor[i] = Some(FLOOR),
+                    ',' => level.floor[i] = Some(FLOOR_CRACKED),
+                    'P' => {
+                        level.floor[i] = Some(FLOOR);
+                        player_spawn = Some(tile);
+                    }
+                    'E' => {
+                        level.floor[i] = Some(FLOOR);
+                        level.enemy_spawns.push(EnemySpawnPoint { tile, enemy: None });
+                    }
+                    'H' => {
+                        level.floor[i] = Some(FLOOR);
+                        level.pickups.push((tile, Pickup { kind: PickupKind::Health, amount: 25 }));
+                    }
+                    'A' => {
+                        level.floor[i] = Some(FLOOR);
+                        level.pickups.push((tile, Pickup { kind: PickupKind::Ammo, amount: 12 }));
+                    }
+                    ' ' => {}
+                    c => anyhow::bail!("unknown tile '{}' at {}, {}", c, x, row),
+                }
+            }
+        }
+        for (row, line) in self.decoration.iter().enumerate().take(height as usize) {
+            let y = height - 1 - row as u32;
+            for (x, c) in line.chars().enumerate().take(width as usize) {
+                let i = level.index(UVec2::new(x as u32, y));
+                level.decoration[i] = match c {
+                    'b' => Some(BONES),
+                    'm' => Some(MOSS),
+                    _ => None,
+                };
+            }
+        }
+        level.player_spawn = match player_spawn {
+            Some(p) => p,
+            None => anyhow::bail!("level \"{}\" has no player start", level.name),
+        };
+        Ok(level)
+    }
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let file: LevelFile = ron::de::from_bytes(bytes)?;
+            let tileset = AssetPath::new(PathBuf::from(&file.tileset), None);
+            let level = file.into_level(load_context.get_handle(tileset.clone()))?;
+            load_context.set_default_asset(LoadedAsset::new(level).with_dependencies(vec![tileset]));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+// level that gets spawned when the game starts
+#[derive(Default)]
+pub struct CurrentLevel(pub Handle<Level>);
+
+impl CurrentLevel {
+    // generated levels are made when the game starts, a level file has to be loaded before it
+    pub fn is_ready(&self, source: &LevelSource, levels: &Assets<Level>) -> bool {
+        matches!(source, LevelSource::Generated(_)) || levels.contains(&self.0)
+    }
+}
+
+// where levels come from, picked on the command line
+pub enum LevelSource {
+    Default,
+    // `--level <path>`, e.g. `--level data/levels/crypt.ldtk#Crypt`
+    File(String),
+    // `--generate` makes a new cave every game, `--seed <u64>` always the same one
+    Generated(Option<u64>),
+}
+
+impl LevelSource {
+    fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let value = |flag: &str| args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|i| args.get(i + 1));
+        if let Some(seed) = value("--seed") {
+            match seed.parse() {
+                Ok(seed) => return LevelSource::Generated(Some(seed)),
+                Err(_) => warn!("--seed needs a number, got \"{}\"", seed),
+            }
+        }
+        if args.iter().any(|arg| arg == "--generate") {
+            return LevelSource::Generated(None);
+        }
+        match value("--level") {
+            Some(path) => LevelSource::File(path.clone()),
+            None => LevelSource::Default,
+        }
+    }
+}
+
+// seed of the current level when it was generated, shown in the pause menu
+#[derive(Default)]
+pub struct LevelSeed(pub Option<u64>);
+
+// everything spawned for the level, tiles aren't children of their tilemap so each one is marked
+#[derive(Component)]
+pub struct LevelEntity;
+
+#[derive(Component)]
+pub struct PlayerSpawn;
+
+// only this enemy type spawns here, any type when `None`
+#[derive(Component)]
+pub struct EnemySpawn(pub Option<EnemyType>);
+
+pub struct LevelPlugin;
+
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(TilemapPlugin);
+        app.init_asset_loader::<LdtkLoader>();
+        app.init_resource::<CurrentLevel>();
+        app.init_resource::<LevelSeed>();
+        app.insert_resource(LevelSource::from_args());
+        app
+            .add_system_set(SystemSet::on_enter(AppState::Menu)
+                .with_system(select_level)
+                .with_system(despawn_level)
+            )
+            .add_system_set(SystemSet::on_update(AppState::Menu)
+                .with_system(report_failed_level)
+            )
+            .add_system_set(SystemSet::on_enter(AppState::Game(GameState::Playing))
+                .with_system(generate_level.before("level"))
+                .with_system(spawn_level.label("level"))
+            )
+            .add_system_set(SystemSet::on_update(AppState::Game(GameState::Playing))
+                .with_system(reload_level.before("broadphase"))
+            );
+    }
+}
+
+fn select_level(
+    mut current: ResMut<CurrentLevel>,
+    source: Res<LevelSource>,
+    data: Res<DataAssets>,
+    asset_server: Res<AssetServer>,
+) {
+    current.0 = match &*source {
+        LevelSource::Default => data.level.clone(),
+        LevelSource::File(path) => asset_server.load(path.as_str()),
+        // made when the game starts, in `generate_level`
+        LevelSource::Generated(_) => return,
+    };
+}
+
+// the game doesn't start without its level, this says why
+fn report_failed_level(
+    current: Res<CurrentLevel>,
+    source: Res<LevelSource>,
+    levels: Res<Assets<Level>>,
+    asset_server: Res<AssetServer>,
+    mut reported: Local<bool>,
+) {
+    let path = match &*source {
+        LevelSource::File(path) => path,
+        _ => return,
+    };
+    if *reported {
+        return;
+    }
+    match asset_server.get_load_state(&current.0) {
+        LoadState::Failed => error!("couldn't load level {}, check the --level path", path),
+        // the file is there but has no level by the name after '#'
+        LoadState::Loaded if !levels.contains(&current.0) => error!("no level {} in its file", path),
+        _ => return,
+    }
+    *reported = true;
+}
+
+fn generate_level(
+    mut current: ResMut<CurrentLevel>,
+    mut seed: ResMut<LevelSeed>,
+    mut levels: ResMut<Assets<Level>>,
+    source: Res<LevelSource>,
+    texture: Res<TextureAssets>,
+) {
+    let level_seed = match *source {
+        LevelSource::Generated(Some(s)) => s,
+        LevelSource::Generated(None) => rand::random(),
+        _ => return,
+    };
+    seed.0 = Some(level_seed);
+    let level = generator::generate(level_seed, texture.tileset.clone(), 16.);
+    info!("generated level with seed {}", level_seed);
+    current.0 = levels.add(level);
+}
+
+fn spawn_level(
+    mut commands: Commands,
+    current: Res<CurrentLevel>,
+    levels: Res<Assets<Level>>,
+    mut textures: ResMut<Assets<Image>>,
+    texture: Res<TextureAssets>,
+) {
+    let level = match levels.get(&current.0) {
+        Some(l) => l,
+        None => {
+            error!("started without a level, see `CurrentLevel::is_ready`");
+            return;
+        }
+    };
+    build_level(&mut commands, level, &mut textures, &texture);
+}
+
+// respawns the level when its file changes on disk, entities other than the level stay where they are
+#[allow(clippy::type_complexity)]
+fn reload_level(
+    mut commands: Commands,
+    mut level_events: EventReader<AssetEvent<Level>>,
+    current: Res<CurrentLevel>,
+    levels: Res<Assets<Level>>,
+    mut textures: ResMut<Assets<Image>>,
+    texture: Res<TextureAssets>,
+    q_ent: Query<Entity, Or<(With<LevelEntity>, With<Pickup>)>>,
+) {
+    let mut modified = false;
+    for event in level_events.iter() {
+        if let AssetEvent::Modified { handle } = event {
+            modified |= handle == &current.0;
+        }
+    }
+    if !modified {
+        return;
+    }
+    let level = match levels.get(&current.0) {
+        Some(l) => l,
+        None => return,
+    };
+    for ent in q_ent.iter() {
+        commands.entity(ent).despawn_recursive();
+    }
+    build_level(&mut commands, level, &mut textures, &texture);
+}
+
+fn build_level(
+    commands: &mut Commands,
+    level: &Level,
+    textures: &mut Assets<Image>,
+    texture: &TextureAssets,
+) {
+    // the tilemap renderer copies tiles out of the tileset, which needs COPY_SRC on the texture
+    if let Some(tileset) = textures.get_mut(&level.tileset) {
+        tileset.texture_descriptor.usage |= TextureUsages::COPY_SRC;
+    }
+    spawn_layer(commands, level, &level.floor, 0.1, false, "Floor");
+    spawn_layer(commands, level, &level.walls, 0.2, true, "Walls");
+    spawn_layer(commands, level, &level.decoration, 0.3, false, "Decoration");
+    commands.spawn_bundle(TransformBundle::from_transform(
+        Transform::from_translation(level.tile_center(level.player_spawn).extend(0.)),
+    ))
+        .insert(PlayerSpawn)
+        .insert(LevelEntity)
+        .insert(Name::new("Player spawn"));
+    for spawn in level.enemy_spawns.iter() {
+        commands.spawn_bundle(TransformBundle::from_transform(
+            Transform::from_translation(level.tile_center(spawn.tile).extend(0.)),
+        ))
+            .insert(EnemySpawn(spawn.enemy))
+            .insert(LevelEntity)
+            .insert(Name::new("Enemy spawn"));
+    }
+    for (tile, pickup) in level.pickups.iter() {
+        spawn_pickup(commands, texture, *pickup, level.tile_center(*tile));
+    }
+    for (tile, marker) in level.markers.iter() {
+        commands.spawn_bundle(TransformBundle::from_transform(
+            Transform::from_translation(level.tile_center(*tile).extend(0.)),
+        ))
+            .insert(marker.clone())
+            .insert(LevelEntity)
+            .insert(Name::new(marker.identifier.clone()));
+    }
+}
+
+// one tilemap per layer, solid layers also get a collider on every tile
+fn spawn_layer(
+    commands: &mut Commands,
+    level: &Level,
+    tiles: &[Option<u32>],
+    z: f32,
+    solid: bool,
+    name: &str,
+) {
+    let size = TilemapSize { x: level.size.x, y: level.size.y };
+    let tilemap = commands.spawn().id();
+    let mut storage = TileStorage::empty(size);
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let cell = UVec2::new(x, y);
+            let texture = match tiles[level.index(cell)] {
+                Some(t) => t,
+                None => continue,
+            };
+            let position = TilePos { x, y };
+            let mut tile = commands.spawn_bundle(TileBundle {
+                position,
+                tilemap_id: TilemapId(tilemap),
+                texture: TileTexture(texture),
+                ..default()
+            });
+            tile.insert(LevelEntity);
+            if solid {
+                tile
+                    .insert(Solid)
+                    .insert(Hitbox::Aabb(Vec2::splat(level.tile_size)))
+                    .insert(Transform::from_translation(level.tile_center(cell).extend(z)));
+            }
+            storage.set(&position, Some(tile.id()));
+        }
+    }
+    let tile_size = TilemapTileSize { x: level.tile_size, y: level.tile_size };
+    commands.entity(tilemap)
+        .insert_bundle(TilemapBundle {
+            grid_size: TilemapGridSize { x: level.tile_size, y: level.tile_size },
+            size,
+            storage,
+            texture: TilemapTexture(level.tileset.clone()),
+            tile_size,
+            // tiles are drawn from their bottom left corner
+            transform: Transform::from_translation(level.origin().extend(z)),
+            ..default()
+        })
+        .insert(LevelEntity)
+        .insert(Name::new(name.to_string()));
+}
+
+fn despawn_level(
+    mut commands: Commands,
+    q_ent: Query<Entity, With<LevelEntity>>,
+) {
+    for ent in q_ent.iter() {
+        commands.entity(ent).despawn_recursive();
+    }
+}
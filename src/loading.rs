@@ -1,6 +1,7 @@
 use bevy::prelude::*;
 use bevy_inspector_egui::WorldInspectorParams;
 use crate::AppState;
+use crate::camera::CameraSettings;
 
 pub struct LoadingPlugin;
 
@@ -23,11 +24,12 @@ fn preload(
     mut commands: Commands,
     mut app_state: ResMut<State<AppState>>,
     asset_server: Res<AssetServer>,
+    camera_settings: Res<CameraSettings>,
     mut wi_params: ResMut<WorldInspectorParams>
 ) {
     commands.spawn_bundle(Camera2dBundle {
         projection: OrthographicProjection {
-            scale: 0.2,
+            scale: camera_settings.zoom,
             ..default()
         },
         ..default()
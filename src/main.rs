@@ -4,17 +4,35 @@ mod menus;
 mod loading;
 mod asset_loader;
 mod ui;
+mod collision;
+mod bench;
+mod weapon;
+mod input;
+mod wave;
+mod level;
+mod navigation;
+mod audio;
+mod animation;
 
+use bevy::asset::AssetServerSettings;
 use bevy::prelude::*;
 use bevy::render::texture::ImageSettings;
 use bevy_inspector_egui::WorldInspectorPlugin;
 use bevy_kira_audio::AudioPlugin;
 use crate::loading::LoadingPlugin;
+use crate::animation::AnimationPlugin;
 use crate::asset_loader::AssetsPlugin;
+use crate::audio::GameAudioPlugin;
+use crate::bench::BenchPlugin;
 use crate::camera::CameraPlugin;
+use crate::collision::CollisionPlugin;
 use crate::entity::EntityPlugin;
+use crate::input::InputPlugin;
+use crate::level::LevelPlugin;
 use crate::menus::MenuPlugin;
+use crate::navigation::NavigationPlugin;
 use crate::ui::UIPlugin;
+use crate::wave::WavePlugin;
 
 fn main() {
     let mut app = App::new();
@@ -27,17 +45,32 @@ fn main() {
         height: 720.0,
         ..default()
     });
+    // level files get reloaded on save while developing
+    app.insert_resource(AssetServerSettings {
+        watch_for_changes: cfg!(debug_assertions),
+        ..default()
+    });
     app.add_plugins(DefaultPlugins);
     app.add_plugin(AudioPlugin);
     app.add_plugin(WorldInspectorPlugin::new());
 
     // own plugins
+    app.add_plugin(InputPlugin);
     app.add_plugin(LoadingPlugin);
     app.add_plugin(AssetsPlugin);
     app.add_plugin(MenuPlugin);
+    app.add_plugin(LevelPlugin);
     app.add_plugin(EntityPlugin);
     app.add_plugin(CameraPlugin);
     app.add_plugin(UIPlugin);
+    app.add_plugin(CollisionPlugin);
+    app.add_plugin(WavePlugin);
+    app.add_plugin(NavigationPlugin);
+    app.add_plugin(GameAudioPlugin);
+    app.add_plugin(AnimationPlugin);
+    if std::env::args().any(|arg| arg == "--bench") {
+        app.add_plugin(BenchPlugin);
+    }
 
     app.run();
 }
@@ -56,4 +89,5 @@ pub enum AppState {
 pub enum GameState {
     Playing,
     Paused,
+    GameOver,
 }
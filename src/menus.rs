@@ -1,7 +1,9 @@
 use bevy::app::AppExit;
 use bevy::prelude::*;
 use crate::{AppState, GameState};
-use crate::asset_loader::TextureAssets;
+use crate::asset_loader::{FontAssets, TextureAssets};
+use crate::input::{Action, Bindings, Rebinding};
+use crate::level::{CurrentLevel, Level, LevelSeed, LevelSource};
 
 pub struct MenuPlugin;
 
@@ -16,7 +18,32 @@ impl Plugin for MenuPlugin {
             )
             .add_system_set(SystemSet::on_exit(AppState::Menu)
                 .with_system(despawn_menu)
+            )
+            .add_system_set(SystemSet::on_enter(AppState::Game(GameState::GameOver))
+                .with_system(spawn_game_over)
+            )
+            .add_system_set(SystemSet::on_update(AppState::Game(GameState::GameOver))
+                .with_system(game_over_input)
+            )
+            .add_system_set(SystemSet::on_exit(AppState::Game(GameState::GameOver))
+                .with_system(despawn_game_over)
+            )
+            .add_system_set(SystemSet::on_update(AppState::Game(GameState::Playing))
+                .with_system(pause_game)
+            )
+            .add_system_set(SystemSet::on_enter(AppState::Game(GameState::Paused))
+                .with_system(enter_pause)
+            )
+            .add_system_set(SystemSet::on_update(AppState::Game(GameState::Paused))
+                .with_system(resume_input)
+                .with_system(pause_button_handling)
+                .with_system(rebuild_pause_menu.after(pause_button_handling))
+                .with_system(update_binding_texts.after(rebuild_pause_menu))
+            )
+            .add_system_set(SystemSet::on_exit(AppState::Game(GameState::Paused))
+                .with_system(despawn_pause)
             );
+        app.init_resource::<PauseView>();
     }
 }
 
@@ -30,6 +57,37 @@ enum ButtonType {
 #[derive(Component)]
 struct MenuUILayer;
 
+#[derive(Component)]
+struct GameOverUILayer;
+
+#[derive(Component)]
+struct PauseUILayer;
+
+#[derive(Component, Clone, Copy)]
+enum PauseButton {
+    Resume,
+    Settings,
+    MainMenu,
+    Quit,
+    Back,
+    Rebind(Action),
+}
+
+// text of a rebind button, shows the action and its current bindings
+#[derive(Component)]
+struct BindingText(Action);
+
+// which page of the pause menu is open
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum PauseView {
+    #[default]
+    Main,
+    Settings,
+}
+
+const PAUSE_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
+const PAUSE_BUTTON_HOVERED: Color = Color::rgb(0.4, 0.4, 0.4);
+
 fn spawn_menu(
     mut commands: Commands,
     texture: Res<TextureAssets>,
@@ -110,6 +168,62 @@ fn despawn_menu(
     }
 }
 
+fn spawn_game_over(
+    mut commands: Commands,
+    fonts: Res<FontAssets>,
+) {
+    commands.spawn_bundle(NodeBundle {
+        color: UiColor(Color::rgba(0., 0., 0., 0.7)),
+        style: Style {
+            size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+            position_type: PositionType::Absolute,
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        ..default()
+    })
+        .insert(GameOverUILayer)
+        .insert(Name::new("Game over"))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(
+                "GAME OVER",
+                TextStyle {
+                    font: fonts.os_bold.clone(),
+                    font_size: 80.0,
+                    color: Color::WHITE,
+                },
+            ));
+            parent.spawn_bundle(TextBundle::from_section(
+                "press Enter to return to menu",
+                TextStyle {
+                    font: fonts.os_regular.clone(),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+}
+
+fn despawn_game_over(
+    mut commands: Commands,
+    q_game_over: Query<Entity, With<GameOverUILayer>>,
+) {
+    for ent in q_game_over.iter() {
+        commands.entity(ent).despawn_recursive();
+    }
+}
+
+fn game_over_input(
+    keys: Res<Input<KeyCode>>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    if keys.just_pressed(KeyCode::Return) {
+        app_state.set(AppState::Menu).unwrap();
+    }
+}
+
 #[allow(clippy::type_complexity)]
 fn button_handling(
     mut app_exit_events: EventWriter<AppExit>,
@@ -119,18 +233,217 @@ fn button_handling(
     >,
     textures: Res<TextureAssets>,
     mut app_state: ResMut<State<AppState>>,
+    level: Res<CurrentLevel>,
+    source: Res<LevelSource>,
+    levels: Res<Assets<Level>>,
 ) {
     for (interaction, button_type, mut image) in q_interaction.iter_mut() {
         match (interaction, button_type) {
-            (Interaction::Clicked, ButtonType::ToGame) => app_state.set(AppState::Game(GameState::Playing)).unwrap(),
+            // a `--level` file that is still loading or failed to, see `report_failed_level`
+            (Interaction::Clicked, ButtonType::ToGame) => if level.is_ready(&source, &levels) {
+                app_state.set(AppState::Game(GameState::Playing)).unwrap();
+            },
             (Interaction::Clicked, ButtonType::ToMenu) => app_state.set(AppState::Menu).unwrap(),
             (Interaction::Clicked, ButtonType::ToQuit) => app_exit_events.send(AppExit),
             (Interaction::Hovered, ButtonType::ToGame) => *image = UiImage(textures.b_start_pressed.clone()),
-            (Interaction::Hovered, ButtonType::ToMenu) => todo!(),
+            (Interaction::Hovered, ButtonType::ToMenu) => {}
             (Interaction::Hovered, ButtonType::ToQuit) => *image = UiImage(textures.b_quit_pressed.clone()),
             (Interaction::None, ButtonType::ToGame) => *image = UiImage(textures.b_start.clone()),
-            (Interaction::None, ButtonType::ToMenu) => todo!(),
+            (Interaction::None, ButtonType::ToMenu) => {}
             (Interaction::None, ButtonType::ToQuit) => *image = UiImage(textures.b_quit.clone()),
         }
     }
-}
\ No newline at end of file
+}
+// pushing keeps Playing on the stack, so its on_exit (HUD despawn) doesn't run and the world stays frozen
+fn pause_game(
+    mut actions: ResMut<Input<Action>>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    if actions.clear_just_pressed(Action::Pause) {
+        // fails when the player died this frame, game over wins
+        let _ = app_state.push(AppState::Game(GameState::Paused));
+    }
+}
+
+fn resume_input(
+    mut actions: ResMut<Input<Action>>,
+    rebinding: Res<Rebinding>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    if rebinding.0.is_none() && actions.clear_just_pressed(Action::Pause) {
+        // the resume button may have popped already this frame
+        let _ = app_state.pop();
+    }
+}
+
+fn enter_pause(
+    mut view: ResMut<PauseView>,
+) {
+    // always set so the change triggers `rebuild_pause_menu`
+    *view = PauseView::Main;
+}
+
+fn despawn_pause(
+    mut commands: Commands,
+    mut rebinding: ResMut<Rebinding>,
+    q_pause: Query<Entity, With<PauseUILayer>>,
+) {
+    rebinding.0 = None;
+    for ent in q_pause.iter() {
+        commands.entity(ent).despawn_recursive();
+    }
+}
+
+fn rebuild_pause_menu(
+    mut commands: Commands,
+    view: Res<PauseView>,
+    fonts: Res<FontAssets>,
+    seed: Res<LevelSeed>,
+    q_pause: Query<Entity, With<PauseUILayer>>,
+) {
+    if !view.is_changed() {
+        return;
+    }
+    for ent in q_pause.iter() {
+        commands.entity(ent).despawn_recursive();
+    }
+    commands.spawn_bundle(NodeBundle {
+        color: UiColor(Color::rgba(0., 0., 0., 0.7)),
+        style: Style {
+            size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+            position_type: PositionType::Absolute,
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        ..default()
+    })
+        .insert(PauseUILayer)
+        .insert(Name::new("Pause menu"))
+        .with_children(|parent| {
+            let title = match *view {
+                PauseView::Main => "PAUSED",
+                PauseView::Settings => "CONTROLS",
+            };
+            parent.spawn_bundle(TextBundle::from_section(
+                title,
+                TextStyle {
+                    font: fonts.os_bold.clone(),
+                    font_size: 60.0,
+                    color: Color::WHITE,
+                },
+            ));
+            match *view {
+                PauseView::Main => {
+                    // so a generated level can be replayed with `--seed`
+                    if let Some(seed) = seed.0 {
+                        parent.spawn_bundle(TextBundle::from_section(
+                            format!("seed {}", seed),
+                            TextStyle {
+                                font: fonts.os_italic.clone(),
+                                font_size: 24.0,
+                                color: Color::WHITE,
+                            },
+                        ));
+                    }
+                    spawn_pause_button(parent, &fonts, PauseButton::Resume, "Resume");
+                    spawn_pause_button(parent, &fonts, PauseButton::Settings, "Settings");
+                    spawn_pause_button(parent, &fonts, PauseButton::MainMenu, "Main Menu");
+                    spawn_pause_button(parent, &fonts, PauseButton::Quit, "Quit");
+                }
+                PauseView::Settings => {
+                    for action in Action::ALL {
+                        spawn_pause_button(parent, &fonts, PauseButton::Rebind(action), action.name());
+                    }
+                    spawn_pause_button(parent, &fonts, PauseButton::Back, "Back");
+                }
+            }
+        });
+}
+
+fn spawn_pause_button(parent: &mut ChildBuilder, fonts: &FontAssets, button: PauseButton, label: &str) {
+    parent.spawn_bundle(ButtonBundle {
+        color: UiColor(PAUSE_BUTTON),
+        style: Style {
+            size: Size::new(Val::Px(360.), Val::Px(34.)),
+            margin: UiRect::all(Val::Px(4.)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        ..default()
+    })
+        .insert(button)
+        .insert(Name::new(format!("{} button", label)))
+        .with_children(|parent| {
+            let mut text = parent.spawn_bundle(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font: fonts.os_regular.clone(),
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                },
+            ));
+            if let PauseButton::Rebind(action) = button {
+                text.insert(BindingText(action));
+            }
+        });
+}
+
+#[allow(clippy::type_complexity)]
+fn pause_button_handling(
+    mut app_exit_events: EventWriter<AppExit>,
+    mut q_interaction: Query<
+        (&Interaction, &PauseButton, &mut UiColor),
+        Changed<Interaction>,
+    >,
+    mut app_state: ResMut<State<AppState>>,
+    mut view: ResMut<PauseView>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    for (interaction, button, mut color) in q_interaction.iter_mut() {
+        match interaction {
+            Interaction::Clicked => match button {
+                // fails when pause was pressed again this frame, which resumes all the same
+                PauseButton::Resume => {
+                    let _ = app_state.pop();
+                }
+                // replace exits the whole stack, Paused and then Playing, before entering Menu,
+                // and wins over a resume queued this frame
+                PauseButton::MainMenu => {
+                    let _ = app_state.overwrite_replace(AppState::Menu);
+                }
+                PauseButton::Quit => app_exit_events.send(AppExit),
+                PauseButton::Settings => *view = PauseView::Settings,
+                PauseButton::Back => {
+                    rebinding.0 = None;
+                    *view = PauseView::Main;
+                }
+                PauseButton::Rebind(action) => rebinding.0 = Some(*action),
+            },
+            Interaction::Hovered => *color = UiColor(PAUSE_BUTTON_HOVERED),
+            Interaction::None => *color = UiColor(PAUSE_BUTTON),
+        }
+    }
+}
+
+fn update_binding_texts(
+    bindings: Res<Bindings>,
+    rebinding: Res<Rebinding>,
+    mut q_text: Query<(&mut Text, &BindingText, ChangeTrackers<BindingText>)>,
+) {
+    let refresh = bindings.is_changed() || rebinding.is_changed();
+    for (mut text, binding_text, tracker) in q_text.iter_mut() {
+        if !refresh && !tracker.is_added() {
+            continue;
+        }
+        let action = binding_text.0;
+        text.sections[0].value = if rebinding.0 == Some(action) {
+            format!("{}: press a key...", action.name())
+        } else {
+            let keys: Vec<String> = bindings.get(action).iter().map(|b| b.name()).collect();
+            format!("{}: {}", action.name(), keys.join(" / "))
+        };
+    }
+}
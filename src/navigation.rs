@@ -0,0 +1,354 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use bevy::prelude::*;
+use crate::{AppState, GameState};
+use crate::collision::Solid;
+use crate::entity::Hitbox;
+use crate::entity::player::Player;
+use crate::level::{CurrentLevel, Level};
+
+// move costs, diagonals are ~sqrt(2) times a straight step
+const STRAIGHT: u32 = 10;
+const DIAGONAL: u32 = 14;
+// seconds between two A* queries of a `PathFollower`
+const REPATH_TIME: f32 = 0.5;
+
+// which tiles of the current level can be walked on, rebuilt whenever a `Solid` appears or disappears,
+// so destroying a wall (removing `Solid` or despawning it) opens it up for navigation
+#[derive(Default)]
+pub struct NavGrid {
+    origin: Vec2,
+    tile_size: f32,
+    size: UVec2,
+    walkable: Vec<bool>,
+}
+
+// steps towards the player from every reachable tile, shared by every enemy
+#[derive(Default)]
+pub struct FlowField {
+    target: Option<UVec2>,
+    // cost to reach the target, u32::MAX when unreachable
+    cost: Vec<u32>,
+}
+
+// enemies with this follow their own A* path instead of the flow field
+#[derive(Component)]
+pub struct PathFollower {
+    // world positions, next one last
+    pub path: Vec<Vec2>,
+    pub repath: Timer,
+}
+
+impl Default for PathFollower {
+    fn default() -> Self {
+        let mut repath = Timer::from_seconds(REPATH_TIME, true);
+        // first path is asked for right away
+        repath.tick(repath.duration());
+        Self {
+            path: Vec::new(),
+            repath,
+        }
+    }
+}
+
+impl PathFollower {
+    pub fn next(&mut self, position: Vec2, reach: f32) -> Option<Vec2> {
+        next_waypoint(&mut self.path, position, reach)
+    }
+}
+
+// next waypoint of a path from `NavGrid::find_path`, the ones already reached get dropped
+pub fn next_waypoint(path: &mut Vec<Vec2>, position: Vec2, reach: f32) -> Option<Vec2> {
+    while let Some(waypoint) = path.last() {
+        if waypoint.distance_squared(position) > reach * reach {
+            return Some(*waypoint);
+        }
+        path.pop();
+    }
+    None
+}
+
+impl NavGrid {
+    pub fn tile_size(&self) -> f32 {
+        self.tile_size
+    }
+
+    pub fn tile(&self, position: Vec2) -> Option<UVec2> {
+        if self.walkable.is_empty() {
+            return None;
+        }
+        let tile = ((position - self.origin) / self.tile_size).floor();
+        if tile.x < 0. || tile.y < 0. || tile.x >= self.size.x as f32 || tile.y >= self.size.y as f32 {
+            return None;
+        }
+        Some(tile.as_uvec2())
+    }
+
+    pub fn center(&self, tile: UVec2) -> Vec2 {
+        self.origin + (tile.as_vec2() + 0.5) * self.tile_size
+    }
+
+    fn index(&self, tile: UVec2) -> usize {
+        (tile.y * self.size.x + tile.x) as usize
+    }
+
+    pub fn is_walkable(&self, tile: UVec2) -> bool {
+        tile.x < self.size.x && tile.y < self.size.y && self.walkable[self.index(tile)]
+    }
+
+    // walkable neighbours with the cost to step there, diagonals can't cut wall corners
+    fn neighbours(&self, tile: UVec2) -> impl Iterator<Item = (UVec2, u32)> + '_ {
+        let t = tile.as_ivec2();
+        let open = move |x: i32, y: i32| {
+            let n = t + IVec2::new(x, y);
+            n.x >= 0 && n.y >= 0 && self.is_walkable(n.as_uvec2())
+        };
+        (-1..=1)
+            .flat_map(|y| (-1..=1).map(move |x| (x, y)))
+            .filter(|&(x, y)| x != 0 || y != 0)
+            .filter(move |&(x, y)| open(x, y) && (x == 0 || y == 0 || (open(x, 0) && open(0, y))))
+            .map(move |(x, y)| {
+                let cost = if x == 0 || y == 0 { STRAIGHT } else { DIAGONAL };
+                ((t + IVec2::new(x, y)).as_uvec2(), cost)
+            })
+    }
+
+    // whether the segment stays clear of walls, sampled every quarter tile
+    pub fn line_of_sight(&self, from: Vec2, to: Vec2) -> bool {
+        let steps = (from.distance(to) / (self.tile_size / 4.)).ceil().max(1.) as u32;
+        (0..=steps).all(|i| {
+            let point = from.lerp(to, i as f32 / steps as f32);
+            self.tile(point).is_none_or(|t| self.is_walkable(t))
+        })
+    }
+
+    // A* between the tiles of two world positions, waypoints are tile centers with the first one last
+    pub fn find_path(&self, from: Vec2, to: Vec2) -> Option<Vec<Vec2>> {
+        let (start, goal) = (self.tile(from)?, self.tile(to)?);
+        if !self.is_walkable(goal) {
+            return None;
+        }
+        let heuristic = |t: UVec2| {
+            let d = (t.as_ivec2() - goal.as_ivec2()).abs();
+            let (min, max) = (d.x.min(d.y) as u32, d.x.max(d.y) as u32);
+            DIAGONAL * min + STRAIGHT * (max - min)
+        };
+        let mut cost = vec![u32::MAX; self.walkable.len()];
+        let mut came_from = vec![None; self.walkable.len()];
+        let mut open = BinaryHeap::new();
+        cost[self.index(start)] = 0;
+        open.push(Reverse((heuristic(start), start.x, start.y)));
+        while let Some(Reverse((_, x, y))) = open.pop() {
+            let tile = UVec2::new(x, y);
+            if tile == goal {
+                let mut path = vec![to];
+                let mut current = tile;
+                while let Some(previous) = came_from[self.index(current)] {
+                    path.push(self.center(current));
+                    current = previous;
+                }
+                return Some(path);
+            }
+            let current_cost = cost[self.index(tile)];
+            for (next, step) in self.neighbours(tile) {
+                let next_cost = current_cost + step;
+                let i = self.index(next);
+                if next_cost < cost[i] {
+                    cost[i] = next_cost;
+                    came_from[i] = Some(tile);
+                    open.push(Reverse((next_cost + heuristic(next), next.x, next.y)));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl FlowField {
+    // direction from `position` towards the next tile on the way to the target
+    pub fn direction(&self, grid: &NavGrid, position: Vec2) -> Option<Vec2> {
+        // built for an older grid
+        if self.cost.len() != grid.walkable.len() {
+            return None;
+        }
+        let tile = grid.tile(position)?;
+        let here = self.cost[grid.index(tile)];
+        let (next, cost) = grid.neighbours(tile)
+            .map(|(n, _)| (n, self.cost[grid.index(n)]))
+            .min_by_key(|(_, cost)| *cost)?;
+        if cost >= here {
+            return None;
+        }
+        Some((grid.center(next) - position).normalize_or_zero())
+    }
+
+    // dijkstra out from the target over the whole grid
+    fn build(&mut self, grid: &NavGrid, target: UVec2) {
+        self.target = Some(target);
+        self.cost = vec![u32::MAX; grid.walkable.len()];
+        if !grid.is_walkable(target) {
+            return;
+        }
+        let mut open = BinaryHeap::new();
+        self.cost[grid.index(target)] = 0;
+        open.push(Reverse((0, target.x, target.y)));
+        while let Some(Reverse((cost, x, y))) = open.pop() {
+            let tile = UVec2::new(x, y);
+            if cost > self.cost[grid.index(tile)] {
+                continue;
+            }
+            for (next, step) in grid.neighbours(tile) {
+                let i = grid.index(next);
+                if cost + step < self.cost[i] {
+                    self.cost[i] = cost + step;
+                    open.push(Reverse((cost + step, next.x, next.y)));
+                }
+            }
+        }
+    }
+}
+
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(SystemSet::on_update(AppState::Game(GameState::Playing))
+                .with_system(rebuild_nav_grid.label("navigation"))
+                .with_system(update_flow_field.label("navigation").after(rebuild_nav_grid))
+            );
+        app.init_resource::<NavGrid>();
+        app.init_resource::<FlowField>();
+    }
+}
+
+fn rebuild_nav_grid(
+    mut grid: ResMut<NavGrid>,
+    current: Res<CurrentLevel>,
+    levels: Res<Assets<Level>>,
+    q_added: Query<(), Added<Solid>>,
+    removed: RemovedComponents<Solid>,
+    q_solid: Query<(&Transform, &Hitbox), With<Solid>>,
+) {
+    if q_added.is_empty() && removed.iter().next().is_none() {
+        return;
+    }
+    let level = match levels.get(&current.0) {
+        Some(l) => l,
+        None => return,
+    };
+    grid.origin = level.origin();
+    grid.tile_size = level.tile_size;
+    grid.size = level.size;
+    grid.walkable = vec![true; (level.size.x * level.size.y) as usize];
+    let (origin, tile_size, last) = (grid.origin, grid.tile_size, grid.size.as_ivec2() - 1);
+    let to_tile = |p: Vec2| ((p - origin) / tile_size).floor().as_ivec2();
+    for (tf, hitbox) in q_solid.iter() {
+        let shape = hitbox.shape(tf.translation.truncate());
+        let half = shape.half_extents();
+        // shrunk a bit so a wall doesn't also block the tiles it only touches
+        let min = to_tile(shape.center() - half + 0.1).max(IVec2::ZERO);
+        let max = to_tile(shape.center() + half - 0.1).min(last);
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let i = grid.index(UVec2::new(x as u32, y as u32));
+                grid.walkable[i] = false;
+            }
+        }
+    }
+}
+
+fn update_flow_field(
+    grid: Res<NavGrid>,
+    mut flow: ResMut<FlowField>,
+    q_player: Query<&Transform, With<Player>>,
+) {
+    let target = match q_player.get_single().ok().and_then(|tf| grid.tile(tf.translation.truncate())) {
+        Some(t) => t,
+        None => return,
+    };
+    // only rebuilt once the player moves to another tile or the walls change
+    if flow.target == Some(target) && !grid.is_changed() {
+        return;
+    }
+    flow.build(&grid, target);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `#` is a wall, first row is y = 0, tiles are 10 wide from the origin
+    fn grid(rows: &[&str]) -> NavGrid {
+        NavGrid {
+            origin: Vec2::ZERO,
+            tile_size: 10.,
+            size: UVec2::new(rows[0].len() as u32, rows.len() as u32),
+            walkable: rows.iter().flat_map(|r| r.chars().map(|c| c != '#')).collect(),
+        }
+    }
+
+    const MAZE: [&str; 5] = [
+        ".....",
+        ".###.",
+        ".#...",
+        ".#.#.",
+        "...#.",
+    ];
+
+    #[test]
+    fn find_path_goes_around_walls() {
+        let grid = grid(&MAZE);
+        let (from, to) = (grid.center(UVec2::new(2, 2)), Vec2::new(41., 43.));
+        let path = grid.find_path(from, to).unwrap();
+        assert_eq!(path[0], to);
+        // one waypoint per step, every one walkable and next to the one before it
+        let mut previous = UVec2::new(2, 2);
+        for waypoint in path.iter().skip(1).rev() {
+            let tile = grid.tile(*waypoint).unwrap();
+            assert!(grid.is_walkable(tile));
+            let d = (tile.as_ivec2() - previous.as_ivec2()).abs();
+            assert!(d.max_element() == 1, "{} -> {}", previous, tile);
+            previous = tile;
+        }
+        assert_eq!(previous, UVec2::new(4, 4));
+        // `to` and (3, 2) -> (4, 2) -> (4, 3) -> (4, 4), the corner at (3, 3) can't be cut
+        assert_eq!(path.len(), 5);
+    }
+
+    #[test]
+    fn find_path_fails_for_walls_and_closed_off_tiles() {
+        let grid = grid(&[
+            "..#..",
+            "..#..",
+            "..#..",
+        ]);
+        assert!(grid.find_path(Vec2::new(5., 5.), Vec2::new(45., 5.)).is_none());
+        assert!(grid.find_path(Vec2::new(5., 5.), Vec2::new(25., 5.)).is_none());
+        assert!(grid.find_path(Vec2::new(5., 5.), Vec2::new(500., 5.)).is_none());
+        assert_eq!(grid.find_path(Vec2::new(5., 5.), Vec2::new(15., 25.)).map(|p| p.len()), Some(3));
+    }
+
+    #[test]
+    fn flow_field_leads_to_target() {
+        let grid = grid(&MAZE);
+        let mut flow = FlowField::default();
+        let target = UVec2::new(4, 4);
+        flow.build(&grid, target);
+        assert_eq!(flow.cost[grid.index(target)], 0);
+        assert_eq!(flow.cost[grid.index(UVec2::new(1, 1))], u32::MAX);
+        assert!(flow.direction(&grid, grid.center(target)).is_none());
+        for start in [UVec2::new(0, 4), UVec2::new(0, 0), UVec2::new(2, 3)] {
+            let mut tile = start;
+            let mut steps = 0;
+            while tile != target {
+                let dir = flow.direction(&grid, grid.center(tile)).unwrap();
+                let next = grid.tile(grid.center(tile) + dir * 7.5).unwrap();
+                assert!(flow.cost[grid.index(next)] < flow.cost[grid.index(tile)]);
+                tile = next;
+                steps += 1;
+                assert!(steps < 25, "stuck going from {}", start);
+            }
+        }
+    }
+}
@@ -1,11 +1,33 @@
 use bevy::prelude::*;
 use crate::{AppState, GameState};
 use crate::asset_loader::FontAssets;
-use crate::entity::player::{Ammo, Gun, UsingGun};
+use crate::entity::Health;
+use crate::entity::boss::Boss;
+use crate::entity::player::{Ammo, Gun, Player, Reloading, UsingGun};
+use crate::wave::{WaveDirector, WavePhase};
 
 #[derive(Component)]
 pub struct AmmoText;
 
+#[derive(Component)]
+pub struct HealthText;
+
+#[derive(Component)]
+pub struct ReloadBar;
+
+#[derive(Component)]
+pub struct WaveText;
+
+// top of the screen, only shown while a boss is alive
+#[derive(Component)]
+pub struct BossBar;
+
+#[derive(Component)]
+pub struct BossNameText;
+
+#[derive(Component)]
+pub struct BossHealthFill;
+
 pub struct UIPlugin;
 
 impl Plugin for UIPlugin {
@@ -16,6 +38,10 @@ impl Plugin for UIPlugin {
             )
             .add_system_set(SystemSet::on_update(AppState::Game(GameState::Playing))
                 .with_system(update_ui.after("shoot"))
+                .with_system(update_health_ui.after("damage"))
+                .with_system(update_reload_ui.after("reload"))
+                .with_system(update_wave_ui.after("waves"))
+                .with_system(update_boss_ui.after("damage"))
             )
             .add_system_set(SystemSet::on_exit(AppState::Game(GameState::Playing))
                 .with_system(despawn_ui)
@@ -64,11 +90,134 @@ fn spawn_ui(
         ..default()
     })
         .insert(AmmoText);
+    commands.spawn_bundle(TextBundle {
+        style: Style {
+            align_self: AlignSelf::FlexEnd,
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                right: Val::Px(10.),
+                top: Val::Px(10.),
+                ..default()
+            },
+            ..default()
+        },
+        text: Text::from_section(
+            "100",
+            TextStyle {
+                font: fonts.os_bold.clone(),
+                font_size: 50.0,
+                color: Color::RED,
+            },
+        ),
+        ..default()
+    })
+        .insert(HealthText);
+    // shown under the player while the gun reloads
+    commands.spawn_bundle(NodeBundle {
+        color: UiColor(Color::WHITE),
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Percent(45.),
+                top: Val::Percent(60.),
+                ..default()
+            },
+            size: Size::new(Val::Percent(0.), Val::Px(6.)),
+            ..default()
+        },
+        ..default()
+    })
+        .insert(ReloadBar);
+    commands.spawn_bundle(TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Percent(45.),
+                top: Val::Px(10.),
+                ..default()
+            },
+            ..default()
+        },
+        text: Text::from_sections(
+            [
+                TextSection::new(
+                    "",
+                    TextStyle {
+                        font: fonts.os_bold.clone(),
+                        font_size: 40.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                TextSection::new(
+                    "",
+                    TextStyle {
+                        font: fonts.os_regular.clone(),
+                        font_size: 30.0,
+                        color: Color::WHITE,
+                    },
+                ),
+            ],
+        ),
+        ..default()
+    })
+        .insert(WaveText);
+    commands.spawn_bundle(NodeBundle {
+        color: UiColor(Color::NONE),
+        style: Style {
+            display: Display::None,
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Percent(25.),
+                top: Val::Px(70.),
+                ..default()
+            },
+            size: Size::new(Val::Percent(50.), Val::Px(50.)),
+            flex_direction: FlexDirection::ColumnReverse,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        ..default()
+    })
+        .insert(BossBar)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::from_section(
+                    "",
+                    TextStyle {
+                        font: fonts.os_bold.clone(),
+                        font_size: 24.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                ..default()
+            })
+                .insert(BossNameText);
+            parent.spawn_bundle(NodeBundle {
+                color: UiColor(Color::rgba(0., 0., 0., 0.6)),
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Px(12.)),
+                    ..default()
+                },
+                ..default()
+            })
+                .with_children(|parent| {
+                    parent.spawn_bundle(NodeBundle {
+                        color: UiColor(Color::rgb(0.7, 0.1, 0.1)),
+                        style: Style {
+                            size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                        .insert(BossHealthFill);
+                });
+        });
 }
 
+#[allow(clippy::type_complexity)]
 fn despawn_ui(
     mut commands: Commands,
-    q_ent: Query<Entity, With<AmmoText>>,
+    q_ent: Query<Entity, Or<(With<AmmoText>, With<HealthText>, With<ReloadBar>, With<WaveText>, With<BossBar>)>>,
 ) {
     for ent in q_ent.iter() {
         commands.entity(ent).despawn_recursive();
@@ -89,4 +238,79 @@ fn update_ui(
     };
     text.sections[0].value = format!("{}/{}", gun.0, gun.1);
     text.sections[2].value = format!("{}", ammo.0);
-}
\ No newline at end of file
+}
+
+fn update_health_ui(
+    mut q_text: Query<&mut Text, With<HealthText>>,
+    q_health: Query<&Health, (With<Player>, Changed<Health>)>,
+) {
+    let health = match q_health.get_single() {
+        Ok(h) => h,
+        Err(_) => return,
+    };
+    let mut text = match q_text.get_single_mut() {
+        Ok(t) => t,
+        Err(_) => return,
+    };
+    text.sections[0].value = format!("{}", health.current);
+}
+
+fn update_reload_ui(
+    mut q_bar: Query<&mut Style, With<ReloadBar>>,
+    q_gun: Query<&Reloading, With<UsingGun>>,
+) {
+    let progress = q_gun.get_single().map(|r| r.progress()).unwrap_or(0.);
+    for mut style in q_bar.iter_mut() {
+        style.size.width = Val::Percent(progress * 10.);
+    }
+}
+fn update_wave_ui(
+    mut q_text: Query<&mut Text, With<WaveText>>,
+    director: Res<WaveDirector>,
+) {
+    let mut text = match q_text.get_single_mut() {
+        Ok(t) => t,
+        Err(_) => return,
+    };
+    text.sections[0].value = if director.wave > 0 {
+        format!("Wave {}", director.wave)
+    } else {
+        String::new()
+    };
+    // countdown to the next wave
+    text.sections[1].value = match &director.phase {
+        WavePhase::Intermission(timer) => {
+            let left = timer.duration().as_secs_f32() - timer.elapsed_secs();
+            format!("\nnext wave in {}", left.ceil() as u32)
+        }
+        _ => String::new(),
+    };
+}
+
+#[allow(clippy::type_complexity)]
+fn update_boss_ui(
+    mut q_bar: Query<&mut Style, (With<BossBar>, Without<BossHealthFill>)>,
+    mut q_fill: Query<&mut Style, (With<BossHealthFill>, Without<BossBar>)>,
+    mut q_text: Query<&mut Text, With<BossNameText>>,
+    q_boss: Query<(&Health, &Name), With<Boss>>,
+) {
+    let boss = q_boss.iter().next();
+    let display = if boss.is_some() { Display::Flex } else { Display::None };
+    for mut style in q_bar.iter_mut() {
+        if style.display != display {
+            style.display = display;
+        }
+    }
+    let (health, name) = match boss {
+        Some(b) => b,
+        None => return,
+    };
+    for mut style in q_fill.iter_mut() {
+        style.size.width = Val::Percent(health.current as f32 / health.max as f32 * 100.);
+    }
+    for mut text in q_text.iter_mut() {
+        if text.sections[0].value != name.as_str() {
+            text.sections[0].value = name.to_string();
+        }
+    }
+}
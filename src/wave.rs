@@ -0,0 +1,231 @@
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+use crate::{AppState, GameState};
+use crate::asset_loader::{AnimationAssets, DataAssets};
+use crate::entity::boss::{Boss, SpawnBossEvent};
+use crate::entity::enemy::{spawn_enemy, Enemy, EnemyType};
+use crate::level::EnemySpawn;
+
+// composition of every wave, waves past the last one repeat it with more enemies
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "3f0b6a2e-5c1d-4e8a-9b7f-2d6c8e4a1f53"]
+pub struct WaveDefinitions {
+    // seconds before the first wave
+    pub first_delay: f32,
+    // seconds between clearing a wave and the next one
+    pub intermission: f32,
+    // how far outside the camera view enemies appear
+    pub spawn_margin: f32,
+    // extra enemies per wave past the last defined one, as a fraction of its counts
+    pub growth: f32,
+    pub waves: Vec<WaveDefinition>,
+}
+
+#[derive(Deserialize)]
+pub struct WaveDefinition {
+    // seconds between two spawns
+    pub spawn_interval: f32,
+    pub groups: Vec<SpawnGroup>,
+    // id in `BossDefinitions`, spawned when the wave starts, only on this exact wave
+    #[serde(default)]
+    pub boss: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SpawnGroup {
+    pub enemy: EnemyType,
+    pub count: u32,
+}
+
+impl WaveDefinitions {
+    // enemies of the wave in spawn order and the time between them, `wave` starts at 1
+    fn wave(&self, wave: u32) -> (Vec<EnemyType>, f32) {
+        let last = self.waves.len() as u32;
+        let definition = match self.waves.get(wave as usize - 1).or_else(|| self.waves.last()) {
+            Some(d) => d,
+            None => return (Vec::new(), 1.),
+        };
+        let scale = 1. + wave.saturating_sub(last) as f32 * self.growth;
+        let mut queue = Vec::new();
+        for group in definition.groups.iter() {
+            let count = (group.count as f32 * scale).round() as u32;
+            queue.extend(std::iter::repeat_n(group.enemy, count as usize));
+        }
+        queue.shuffle(&mut rand::thread_rng());
+        (queue, definition.spawn_interval)
+    }
+}
+
+#[derive(Default)]
+pub struct WavesLoader;
+
+impl AssetLoader for WavesLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let definitions: WaveDefinitions = ron::de::from_bytes(bytes)?;
+            // a repeating timer of zero panics on its first tick
+            for (i, wave) in definitions.waves.iter().enumerate() {
+                if wave.spawn_interval.is_nan() || wave.spawn_interval <= 0. {
+                    anyhow::bail!("wave {} has a spawn_interval of {}, it has to be above 0", i + 1, wave.spawn_interval);
+                }
+            }
+            if definitions.intermission.is_nan() || definitions.intermission < 0. {
+                anyhow::bail!("intermission of {} can't be negative", definitions.intermission);
+            }
+            load_context.set_default_asset(LoadedAsset::new(definitions));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["waves.ron"]
+    }
+}
+
+pub enum WavePhase {
+    // counting down to the next wave
+    Intermission(Timer),
+    // enemies left to spawn, one every time the timer finishes
+    Spawning(Vec<EnemyType>, Timer),
+    // everything is spawned, waiting for the wave to be cleared
+    Fighting,
+}
+
+pub struct WaveDirector {
+    // current wave, 0 before the first one
+    pub wave: u32,
+    pub phase: WavePhase,
+}
+
+impl Default for WaveDirector {
+    fn default() -> Self {
+        Self {
+            wave: 0,
+            phase: WavePhase::Intermission(Timer::from_seconds(0., false)),
+        }
+    }
+}
+
+pub struct WavePlugin;
+
+impl Plugin for WavePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(SystemSet::on_enter(AppState::Game(GameState::Playing))
+                .with_system(reset_director)
+            )
+            .add_system_set(SystemSet::on_update(AppState::Game(GameState::Playing))
+                .with_system(run_director.label("waves").after("camera"))
+            );
+        app.init_resource::<WaveDirector>();
+    }
+}
+
+fn reset_director(
+    mut director: ResMut<WaveDirector>,
+    data: Res<DataAssets>,
+    wave_defs: Res<Assets<WaveDefinitions>>,
+) {
+    let waves = wave_defs.get(&data.waves).unwrap();
+    *director = WaveDirector {
+        wave: 0,
+        phase: WavePhase::Intermission(Timer::from_seconds(waves.first_delay, false)),
+    };
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn run_director(
+    mut commands: Commands,
+    mut director: ResMut<WaveDirector>,
+    mut boss_events: EventWriter<SpawnBossEvent>,
+    data: Res<DataAssets>,
+    wave_defs: Res<Assets<WaveDefinitions>>,
+    animations: Res<AnimationAssets>,
+    windows: Res<Windows>,
+    q_camera: Query<(&Transform, &OrthographicProjection), With<Camera2d>>,
+    q_enemy: Query<(), Or<(With<Enemy>, With<Boss>)>>,
+    q_spawn: Query<(&Transform, &EnemySpawn)>,
+    time: Res<Time>,
+) {
+    let waves = match wave_defs.get(&data.waves) {
+        Some(w) => w,
+        None => return,
+    };
+    let director = &mut *director;
+    match &mut director.phase {
+        WavePhase::Intermission(timer) => {
+            if timer.tick(time.delta()).finished() {
+                director.wave += 1;
+                let (queue, interval) = waves.wave(director.wave);
+                if let Some(boss) = waves.waves.get(director.wave as usize - 1).and_then(|w| w.boss.clone()) {
+                    boss_events.send(SpawnBossEvent { id: boss });
+                }
+                director.phase = WavePhase::Spawning(queue, Timer::from_seconds(interval, true));
+            }
+        }
+        WavePhase::Spawning(queue, timer) => {
+            let spawns = timer.tick(time.delta()).times_finished_this_tick();
+            let view = match (windows.get_primary(), q_camera.get_single()) {
+                (Some(window), Ok((tf, projection))) => Some((
+                    tf.translation.truncate(),
+                    Vec2::new(window.width(), window.height()) / 2. * projection.scale,
+                )),
+                _ => None,
+            };
+            for _ in 0..spawns {
+                let enemy_type = match queue.pop() {
+                    Some(e) => e,
+                    None => break,
+                };
+                let markers: Vec<Vec2> = q_spawn.iter()
+                    .filter(|(_, spawn)| spawn.0.is_none_or(|e| e == enemy_type))
+                    .map(|(tf, _)| tf.translation.truncate())
+                    .collect();
+                let position = match view {
+                    Some((center, half)) => spawn_point(&markers, center, half, waves.spawn_margin),
+                    None => Vec2::ZERO,
+                };
+                spawn_enemy(&mut commands, &animations, enemy_type, position);
+            }
+            if queue.is_empty() {
+                director.phase = WavePhase::Fighting;
+            }
+        }
+        WavePhase::Fighting => {
+            if q_enemy.is_empty() {
+                director.phase = WavePhase::Intermission(Timer::from_seconds(waves.intermission, false));
+            }
+        }
+    }
+}
+
+// random level spawn marker outside the camera view, or a random point on a rectangle
+// `margin` outside the view when the level has none
+fn spawn_point(markers: &[Vec2], center: Vec2, half: Vec2, margin: f32) -> Vec2 {
+    let mut rng = rand::thread_rng();
+    let hidden: Vec<Vec2> = markers.iter()
+        .filter(|m| (**m - center).abs().cmpgt(half).any())
+        .copied()
+        .collect();
+    // every marker is on screen, better to spawn in view than inside a wall
+    if let Some(marker) = hidden.choose(&mut rng).or_else(|| markers.choose(&mut rng)) {
+        return *marker;
+    }
+    let half = half + Vec2::splat(margin);
+    let side = if rng.gen_bool(0.5) { 1. } else { -1. };
+    // pick the side weighted by its length so spawns are spread evenly around the view
+    if rng.gen_range(0. ..half.x + half.y) < half.x {
+        Vec2::new(rng.gen_range(-half.x..=half.x), side * half.y) + center
+    } else {
+        Vec2::new(side * half.x, rng.gen_range(-half.y..=half.y)) + center
+    }
+}
@@ -0,0 +1,197 @@
+use std::path::PathBuf;
+use bevy::asset::{Asset, AssetLoader, AssetPath, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use bevy_kira_audio::AudioSource;
+use serde::Deserialize;
+use crate::animation::AnimationSet;
+use crate::entity::Team;
+use crate::entity::bullet::BulletType;
+
+// every weapon in the game, the player's in the order they sit on the belt
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "616a70f5-82cf-48d5-8c29-4806db300c0c"]
+pub struct WeaponDefinitions {
+    pub weapons: Vec<WeaponDefinition>,
+    // fired by enemies, see `EnemyStats::weapon`
+    #[serde(default)]
+    pub enemy_weapons: Vec<WeaponDefinition>,
+    #[serde(default)]
+    pub friendly_fire: FriendlyFire,
+}
+
+impl WeaponDefinitions {
+    pub fn get(&self, id: &str) -> Option<&WeaponDefinition> {
+        self.weapons.iter().chain(self.enemy_weapons.iter()).find(|w| w.id == id)
+    }
+}
+
+// whether bullets and explosions hurt the team that fired them
+#[derive(Deserialize, Default, Clone, Copy)]
+pub struct FriendlyFire {
+    #[serde(default)]
+    pub player: bool,
+    #[serde(default)]
+    pub enemy: bool,
+}
+
+impl FriendlyFire {
+    pub fn allows(&self, team: Team) -> bool {
+        match team {
+            Team::Player => self.player,
+            Team::Enemy => self.enemy,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WeaponDefinition {
+    pub id: String,
+    pub name: String,
+    // path of the gun's `AnimationSet`, enemy weapons have no gun to show
+    #[serde(default)]
+    pub gun_animation: String,
+    pub bullet_texture: String,
+    pub bullet: BulletType,
+    // where the gun sits relative to the player
+    pub offset: (f32, f32),
+    pub magazine: u32,
+    pub reserve: u32,
+    pub fire_mode: FireMode,
+    // shots per second
+    pub fire_rate: f32,
+    pub bullet_speed: f32,
+    pub range: f32,
+    pub damage: u32,
+    // max deviation from the aim direction, in degrees
+    pub spread: f32,
+    // bullets per shot
+    pub pellets: u32,
+    // seconds for a full reload, or for a single shell when `per_shell` is set
+    pub reload_time: f32,
+    pub per_shell: bool,
+    pub reload_animation: ReloadAnimation,
+    // kick, bloom and shake of every shot, a weapon without one doesn't move
+    #[serde(default)]
+    pub recoil: RecoilDefinition,
+    // bullets of weapons with an explosion detonate on impact and at max range
+    #[serde(default)]
+    pub explosion: Option<ExplosionDefinition>,
+    // sound paths, a weapon without one stays silent
+    #[serde(default)]
+    pub fire_sound: String,
+    #[serde(default)]
+    pub reload_sound: String,
+    #[serde(default)]
+    pub empty_sound: String,
+    #[serde(skip)]
+    pub gun_clips: Handle<AnimationSet>,
+    #[serde(skip)]
+    pub bullet_image: Handle<Image>,
+    #[serde(skip)]
+    pub fire_audio: Option<Handle<AudioSource>>,
+    #[serde(skip)]
+    pub reload_audio: Option<Handle<AudioSource>>,
+    #[serde(skip)]
+    pub empty_audio: Option<Handle<AudioSource>>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub enum FireMode {
+    // one shot per click
+    Semi,
+    // fires while the trigger is held
+    Automatic,
+    // rounds fired per click
+    Burst(u32),
+    // seconds the trigger has to be held, fires on release
+    Charge(f32),
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+pub struct RecoilDefinition {
+    // how far the gun is pushed back along the aim, in pixels
+    #[serde(default)]
+    pub kick: f32,
+    // speed taken off the player in the aim direction
+    #[serde(default)]
+    pub push: f32,
+    // degrees added to `spread` by every shot, up to `max_bloom`
+    #[serde(default)]
+    pub bloom: f32,
+    #[serde(default)]
+    pub max_bloom: f32,
+    // degrees of bloom lost per second
+    #[serde(default)]
+    pub recovery: f32,
+    // camera trauma added by every shot, a full shake is 1
+    #[serde(default)]
+    pub shake: f32,
+}
+
+#[derive(Deserialize)]
+pub struct ExplosionDefinition {
+    pub radius: f32,
+    // damage at the center, falls off linearly to 0 at `radius`
+    pub damage: u32,
+    // impulse added to `Motion` at the center
+    pub knockback: f32,
+    // whether the shooter can hurt themselves
+    pub self_damage: bool,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub enum ReloadAnimation {
+    // gun drops down and comes back up
+    Dip,
+    // gun does a full turn
+    Spin,
+}
+
+#[derive(Default)]
+pub struct WeaponsLoader;
+
+impl AssetLoader for WeaponsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let mut definitions: WeaponDefinitions = ron::de::from_bytes(bytes)?;
+            let mut dependencies = Vec::new();
+            for weapon in definitions.weapons.iter_mut().chain(definitions.enemy_weapons.iter_mut()) {
+                if let Some(clips) = optional_handle(load_context, &mut dependencies, &weapon.gun_animation) {
+                    weapon.gun_clips = clips;
+                }
+                let bullet = AssetPath::new(PathBuf::from(&weapon.bullet_texture), None);
+                weapon.bullet_image = load_context.get_handle(bullet.clone());
+                dependencies.push(bullet);
+                weapon.fire_audio = optional_handle(load_context, &mut dependencies, &weapon.fire_sound);
+                weapon.reload_audio = optional_handle(load_context, &mut dependencies, &weapon.reload_sound);
+                weapon.empty_audio = optional_handle(load_context, &mut dependencies, &weapon.empty_sound);
+            }
+            load_context.set_default_asset(LoadedAsset::new(definitions).with_dependencies(dependencies));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["weapons.ron"]
+    }
+}
+
+// handle to the asset at `path` unless it's empty
+fn optional_handle<T: Asset>(
+    load_context: &LoadContext,
+    dependencies: &mut Vec<AssetPath<'static>>,
+    path: &str,
+) -> Option<Handle<T>> {
+    if path.is_empty() {
+        return None;
+    }
+    let path = AssetPath::new(PathBuf::from(path), None);
+    dependencies.push(path.clone());
+    Some(load_context.get_handle(path))
+}